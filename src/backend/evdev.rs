@@ -1,109 +1,1275 @@
-use crate::{backend::Event, key::Key};
+use crate::{
+    backend::{
+        uinput::{VirtualDevice, VirtualDeviceBuilder},
+        DeviceInfo, Event, Signal, Source, Stats, StopSignal, Verdict,
+    },
+    config::DeviceMatcher,
+    key::{Axis, Key},
+};
 use crossbeam_channel::{Receiver, Sender, TrySendError};
-use evdev::{Device, InputEventKind, Key as EvKey};
+use evdev::{
+    raw_stream::RawDevice, AbsoluteAxisType, AttributeSet, EventType, InputEvent, InputEventKind,
+    Key as EvKey, RelativeAxisType, SwitchType, Synchronization,
+};
+use glob::Pattern;
+use nix::{
+    errno::Errno,
+    poll::{poll, PollFd, PollFlags},
+    sys::inotify::{AddWatchFlags, InitFlags, Inotify},
+};
+use regex::Regex;
 use std::{
+    collections::{HashMap, HashSet},
     fmt::{Display, Formatter},
-    io,
+    fs, io,
     io::ErrorKind,
+    mem,
+    os::unix::io::{AsRawFd, RawFd},
     path::{Path, PathBuf},
+    sync::{atomic::Ordering, Arc},
     thread,
     thread::JoinHandle,
-    time::Duration,
+    time::{Instant, SystemTime},
 };
 
 const RETRY_PERIOD_MILLIS: u64 = 1000;
 
+const DEVICE_DIR: &str = "/dev/input";
+
 const RELEASED: i32 = 0;
 const PRESSED: i32 = 1;
 const HELD: i32 = 2;
 
-pub fn start(dev_path: PathBuf, retry: bool) -> (Receiver<Event>, JoinHandle<()>) {
+const SWITCH_OFF: i32 = 0;
+const SWITCH_ON: i32 = 1;
+
+const VIRTUAL_DEVICE_NAME: &str = "systemchord virtual keyboard";
+
+pub fn start(
+    sources: Vec<(Source, DeviceMatcher)>,
+    retry: bool,
+    verdicts: Option<Receiver<Verdict>>,
+    stop: StopSignal,
+    stats: Arc<Stats>,
+) -> (Receiver<Event>, JoinHandle<()>) {
     log::trace!("Starting keyboard polling thread");
     let (sender, receiver) = crossbeam_channel::bounded(1024);
     let handle = thread::spawn(move || {
-        if retry {
-            let mut opened = false;
-            loop {
-                match open_and_poll(&dev_path, sender.clone(), Some(&mut opened)) {
-                    Err(Error::NotFound | Error::Disconnected) => {
-                        log::debug!(
-                            "Device file not found, retrying in {} milliseconds",
-                            RETRY_PERIOD_MILLIS
-                        );
-                        if opened {
-                            log::info!("Keyboard device unavailable. Retrying periodically...");
-                            if sender.send(Event::Stop).is_err() {
-                                log::error!("{}", Error::Hangup);
-                                break;
-                            }
-                            opened = false;
-                        }
-                        thread::sleep(Duration::from_millis(RETRY_PERIOD_MILLIS));
-                    }
-                    Err(other) => {
-                        log::error!("An unrecoverable error occurred: {other}");
-                        break;
-                    }
-                    Ok(_) => unreachable!(),
-                }
-            }
-        } else {
-            let err = open_and_poll(&dev_path, sender, None).unwrap_err();
-            log::error!("An error occurred in the backend: {err}");
+        let hotplug = if retry { Hotplug::new(&sources) } else { None };
+        let mut reader = Reader {
+            sources,
+            retry,
+            sender,
+            verdicts: verdicts.as_ref(),
+            hotplug,
+            inputs: Vec::new(),
+            held: Held::default(),
+            stats,
+        };
+        match reader.run(&stop).unwrap_err() {
+            Error::Stopped => log::debug!("{}", Error::Stopped),
+            err => log::error!("An unrecoverable error occurred: {err}"),
         }
     });
     (receiver, handle)
 }
 
-fn open_and_poll(
-    dev_path: &Path,
+/// Reads the devices matching each source. With `retry`, devices are opened whenever they appear
+/// and may disappear again; otherwise they must all be there from the start and stay.
+struct Reader<'a> {
+    sources: Vec<(Source, DeviceMatcher)>,
+    retry: bool,
     sender: Sender<Event>,
-    opened: Option<&mut bool>,
-) -> Result<Never, Error> {
-    log::debug!("Opening device {}", dev_path.to_string_lossy());
-    let mut dev = Device::open(dev_path)?;
-    if let Some(opened) = opened {
-        *opened = true;
-    }
-    loop {
-        let events = dev.fetch_events().map_err(|_| Error::Disconnected)?;
-        for (keycode, raw_event) in events.filter_map(|event| match event.kind() {
-            InputEventKind::Key(key) => Some((key, event)),
-            _ => None,
-        }) {
-            let Some(key) = map_key(keycode) else {
-                log::debug!("Unsupported keycode {}", keycode.code());
-                continue;
+    verdicts: Option<&'a Receiver<Verdict>>,
+    /// Without it, missing devices are looked for periodically instead.
+    hotplug: Option<Hotplug>,
+    inputs: Vec<Input<'a>>,
+    held: Held,
+    stats: Arc<Stats>,
+}
+
+impl Reader<'_> {
+    fn run(&mut self, stop: &StopSignal) -> Result<Never, Error> {
+        let mut rescan = true;
+        loop {
+            if rescan && self.connect()? {
+                self.sync()?;
+            }
+            let mut fds = vec![stop.as_raw_fd()];
+            fds.extend(
+                self.hotplug
+                    .as_ref()
+                    .map(|hotplug| hotplug.inotify.as_raw_fd()),
+            );
+            let first_input = fds.len();
+            fds.extend(self.inputs.iter().map(|input| input.dev.as_raw_fd()));
+            let first_output = fds.len();
+            let outputs = self
+                .inputs
+                .iter()
+                .filter_map(|input| Some(input.grab.as_ref()?.output.as_raw_fd()))
+                .collect::<Vec<_>>();
+            fds.extend(&outputs);
+            let timeout = if self.retry && self.hotplug.is_none() {
+                RETRY_PERIOD_MILLIS as i32
+            } else {
+                -1
+            };
+            // keys held back are replayed at their deadline, even if nothing else happens
+            let held_back_until = self
+                .inputs
+                .iter()
+                .filter_map(|input| input.grab.as_ref()?.filter.held_back_until)
+                .min();
+            let timeout = match held_back_until {
+                Some(until) => {
+                    let left = until.saturating_duration_since(Instant::now());
+                    // rounded up, so that the deadline has passed once woken
+                    let left = left.as_millis() as i32 + 1;
+                    if timeout < 0 {
+                        left
+                    } else {
+                        timeout.min(left)
+                    }
+                }
+                None => timeout,
             };
 
-            let event = match raw_event.value() {
-                RELEASED => Event::Released(key),
-                PRESSED => Event::Pressed(key),
-                HELD => {
+            let ready = wait_readable(&fds, timeout)?;
+            if ready[0] {
+                match stop.read() {
+                    Signal::Stop => return Err(Error::Stopped),
+                    Signal::Resync => self.sync()?,
+                }
+            }
+            for input in &mut self.inputs {
+                if let Some(grab) = &mut input.grab {
+                    grab.expire(Instant::now())?;
+                }
+            }
+            // timed out
+            rescan = ready.iter().all(|ready| !ready);
+            if let Some(hotplug) = &mut self.hotplug {
+                if ready[1] {
+                    hotplug.drain();
+                    rescan = true;
+                }
+            }
+            // only grabbing inputs have an output, so they line up when there are any
+            for (index, ready) in ready[first_output..].iter().enumerate() {
+                if *ready {
+                    self.inputs[index].forward_leds();
+                }
+            }
+            let mut dropped = false;
+            // backwards, so that disconnecting does not shift the inputs still to read
+            for index in (0..self.inputs.len()).rev() {
+                if !ready[first_input + index] {
                     continue;
                 }
-                o => {
-                    log::warn!("Unexpected event value `{o}` for key {}", keycode.code());
+                match self.inputs[index].read(&self.sender, &mut self.held, &self.stats) {
+                    Ok(lost) => dropped |= lost,
+                    Err(Error::Disconnected) => self.disconnect(index)?,
+                    Err(err) => return Err(err),
+                }
+            }
+            if dropped {
+                log::debug!("Events were lost, resynchronizing held keys");
+                self.sync()?;
+            }
+        }
+    }
+
+    /// Opens the matching devices that are not open yet, returning whether there were any.
+    fn connect(&mut self) -> Result<bool, Error> {
+        let mut connected = false;
+        for index in 0..self.sources.len() {
+            let (source, devices) = &self.sources[index];
+            let source = source.clone();
+            let paths = match resolve(devices) {
+                Ok(paths) => paths,
+                Err(Error::NotFound) if self.retry => {
+                    match source {
+                        Some(input) => log::debug!("No device found for input `{input}` yet"),
+                        None => log::debug!("No device found, waiting for one to appear"),
+                    }
+                    continue;
+                }
+                Err(err) => return Err(err),
+            };
+            for path in paths {
+                connected |= self.open(&source, path)?;
+            }
+        }
+        Ok(connected)
+    }
+
+    /// Opens a device for a source unless it is open already, returning whether it was opened.
+    fn open(&mut self, source: &Source, path: PathBuf) -> Result<bool, Error> {
+        // a device matched by several inputs belongs to the first of them
+        if self.inputs.iter().any(|input| input.path == path) {
+            return Ok(false);
+        }
+        match Input::open(&path, source.clone(), self.verdicts) {
+            Ok(input) => {
+                log::info!(
+                    "Device {} arrived: {}{}",
+                    path.to_string_lossy(),
+                    input.dev.name().unwrap_or("(unnamed)"),
+                    source
+                        .as_ref()
+                        .map(|input| format!(" (input `{input}`)"))
+                        .unwrap_or_default()
+                );
+                self.inputs.push(input);
+                Ok(true)
+            }
+            Err(Error::NotFound) if self.retry => {
+                log::debug!("Device {} is not there yet", path.to_string_lossy());
+                Ok(false)
+            }
+            Err(Error::PermissionDenied) if self.retry => {
+                log::warn!(
+                    "Not allowed to open device {}, waiting for its permissions to change",
+                    path.to_string_lossy()
+                );
+                Ok(false)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Replaces the held keys and switches with those the kernel reports, since keys may already
+    /// be down and switches on when a device is opened, and events may have been lost.
+    fn sync(&mut self) -> Result<(), Error> {
+        self.held = Held::default();
+        // backwards, so that disconnecting does not shift the inputs still to read
+        for index in (0..self.inputs.len()).rev() {
+            let input = &mut self.inputs[index];
+            let state = input.dev.get_key_state().and_then(|pressed| {
+                let switches = match input.dev.supported_switches() {
+                    Some(_) => input.dev.get_switch_state()?,
+                    None => AttributeSet::new(),
+                };
+                Ok((pressed.iter().collect::<HashSet<_>>(), switches))
+            });
+            let (pressed, switches) = match state {
+                Ok(state) => state,
+                // unplugged since the events were lost
+                Err(err) => {
+                    log::debug!(
+                        "Cannot read the state of device {}: {err}",
+                        input.path.to_string_lossy()
+                    );
+                    // the keys it held are left out of the sync rather than released
+                    input.pressed.clear();
+                    input.switches = AttributeSet::new();
+                    self.disconnect(index)?;
                     continue;
                 }
             };
-            match sender.try_send(event) {
-                Err(TrySendError::Full(_)) => {
-                    log::warn!("Overflowed capacity, events may be dropped.");
+            if let Some(grab) = &mut input.grab {
+                grab.sync(&input.pressed, &pressed, &input.switches, &switches)?;
+            }
+            let keys = pressed.iter().map(|keycode| map_key(*keycode));
+            for key in keys.chain(switches.iter().filter_map(map_switch)) {
+                self.held.press(&input.source, key);
+            }
+            input.pressed = pressed;
+            input.switches = switches;
+        }
+        let keys = self
+            .held
+            .0
+            .keys()
+            .map(|(source, key)| (*key, source.clone()));
+        self.sender
+            .send(Event::Sync(keys.collect()))
+            .map_err(|_| Error::Hangup)
+    }
+
+    /// Forgets a device that went away, releasing the keys and switches it held.
+    fn disconnect(&mut self, index: usize) -> Result<(), Error> {
+        let input = self.inputs.remove(index);
+        log::info!("Device {} left", input.path.to_string_lossy());
+        if !self.retry {
+            return Err(Error::Disconnected);
+        }
+        if self.inputs.is_empty() {
+            log::info!("No device left, waiting for one to appear");
+            self.held = Held::default();
+            return self.sender.send(Event::Stop).map_err(|_| Error::Hangup);
+        }
+        let keys = input.pressed.iter().map(|keycode| map_key(*keycode));
+        for key in keys.chain(input.switches.iter().filter_map(map_switch)) {
+            if self.held.release(&input.source, key) {
+                self.sender
+                    .send(Event::Released(key, input.source.clone(), Instant::now()))
+                    .map_err(|_| Error::Hangup)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Watches the device directories, so that devices are opened as soon as they appear.
+struct Hotplug {
+    inotify: Inotify,
+    /// Directories that could not be watched yet, like `/dev/input/by-id` before the first device
+    /// with an ID appeared.
+    unwatched: Vec<PathBuf>,
+}
+
+impl Hotplug {
+    const FLAGS: AddWatchFlags = AddWatchFlags::IN_CREATE
+        .union(AddWatchFlags::IN_ATTRIB)
+        .union(AddWatchFlags::IN_MOVED_TO);
+
+    fn new(sources: &[(Source, DeviceMatcher)]) -> Option<Self> {
+        let inotify = match Inotify::init(InitFlags::IN_CLOEXEC | InitFlags::IN_NONBLOCK) {
+            Ok(inotify) => inotify,
+            Err(err) => {
+                log::warn!("Cannot watch for new devices, looking for them periodically: {err}");
+                return None;
+            }
+        };
+        if let Err(err) = inotify.add_watch(DEVICE_DIR, Self::FLAGS) {
+            log::warn!(
+                "Cannot watch {DEVICE_DIR} for new devices, looking for them periodically: {err}"
+            );
+            return None;
+        }
+        let mut hotplug = Hotplug {
+            inotify,
+            unwatched: Vec::new(),
+        };
+        // symlinks like those in `/dev/input/by-id` appear a bit after the device itself
+        let mut dirs = Vec::new();
+        for (_, devices) in sources {
+            if let Some(dir) = devices.device.as_ref().and_then(|device| device.parent()) {
+                dirs.push(dir.to_path_buf());
+            }
+            if devices.path_glob.is_some() {
+                dirs.push(Path::new(DEVICE_DIR).join("by-id"));
+                dirs.push(Path::new(DEVICE_DIR).join("by-path"));
+            }
+        }
+        dirs.retain(|dir| dir != Path::new(DEVICE_DIR));
+        dirs.sort();
+        dirs.dedup();
+        for dir in dirs {
+            if let Err(err) = hotplug.inotify.add_watch(&dir, Self::FLAGS) {
+                log::warn!(
+                    "Cannot watch {} for new devices yet: {err}",
+                    dir.to_string_lossy()
+                );
+                hotplug.unwatched.push(dir);
+            }
+        }
+        Some(hotplug)
+    }
+
+    fn drain(&mut self) {
+        match self.inotify.read_events() {
+            Ok(events) => {
+                for event in events {
+                    log::debug!(
+                        "Device directory changed: {:?} {:?}",
+                        event.mask,
+                        event.name
+                    );
+                }
+            }
+            Err(Errno::EAGAIN) => {}
+            Err(err) => log::warn!("Failed to read device directory changes: {err}"),
+        }
+        // the directories missing at first may have been created since
+        let inotify = &self.inotify;
+        self.unwatched.retain(|dir| {
+            let watched = inotify.add_watch(dir, Self::FLAGS).is_ok();
+            if watched {
+                log::debug!("Watching {} for new devices", dir.to_string_lossy());
+            }
+            !watched
+        });
+    }
+}
+
+impl Drop for Hotplug {
+    fn drop(&mut self) {
+        let _ = nix::unistd::close(self.inotify.as_raw_fd());
+    }
+}
+
+/// Finds the paths of the devices to read, scanning `/dev/input` unless a fixed path was given.
+fn resolve(devices: &DeviceMatcher) -> Result<Vec<PathBuf>, Error> {
+    if let Some(path) = devices.fixed_path() {
+        return Ok(vec![path.clone()]);
+    }
+    // both were checked when validating the config
+    let name_regex = devices
+        .name_regex
+        .as_deref()
+        .and_then(|name_regex| Regex::new(name_regex).ok());
+    let path_glob = devices
+        .path_glob
+        .as_deref()
+        .and_then(|path_glob| Pattern::new(path_glob).ok());
+    let device = devices
+        .device
+        .as_ref()
+        .map(|device| fs::canonicalize(device).unwrap_or_else(|_| device.clone()));
+
+    let paths = list_devices()?
+        .into_iter()
+        .filter(|info| {
+            let name = info.name.as_deref();
+            name != Some(VIRTUAL_DEVICE_NAME)
+                && device.as_ref().is_none_or(|device| *device == info.path)
+                && devices
+                    .name
+                    .as_deref()
+                    .is_none_or(|wanted| name == Some(wanted))
+                && name_regex
+                    .as_ref()
+                    .is_none_or(|name_regex| name.is_some_and(|name| name_regex.is_match(name)))
+                && devices
+                    .vendor
+                    .is_none_or(|vendor| info.vendor == Some(vendor))
+                && devices
+                    .product
+                    .is_none_or(|product| info.product == Some(product))
+                && devices
+                    .phys
+                    .as_ref()
+                    .is_none_or(|phys| info.phys.as_ref() == Some(phys))
+                && path_glob.as_ref().is_none_or(|path_glob| {
+                    std::iter::once(&info.path)
+                        .chain(&info.by_id)
+                        .chain(&info.by_path)
+                        .any(|path| path_glob.matches_path(path))
+                })
+        })
+        .map(|info| info.path)
+        .collect::<Vec<_>>();
+    if paths.is_empty() {
+        return Err(Error::NotFound);
+    }
+    Ok(paths)
+}
+
+/// One opened device of the backend.
+struct Input<'a> {
+    path: PathBuf,
+    /// The source its events are reported from.
+    source: Source,
+    dev: RawDevice,
+    grab: Option<Grab<'a>>,
+    /// Keys this device holds down, to release them if it disappears.
+    pressed: HashSet<EvKey>,
+    /// Switches this device has on, likewise.
+    switches: AttributeSet<SwitchType>,
+    /// Whether events are being skipped until the end of a report the kernel dropped events in.
+    dropping: bool,
+    axes: Axes,
+}
+
+impl<'a> Input<'a> {
+    fn open(
+        path: &Path,
+        source: Source,
+        verdicts: Option<&'a Receiver<Verdict>>,
+    ) -> Result<Self, Error> {
+        log::debug!("Opening device {}", path.to_string_lossy());
+        let mut dev = RawDevice::open(path)?;
+        let axes = Axes::new(&dev)?;
+        let grab = match verdicts {
+            Some(verdicts) => Some(Grab::new(&mut dev, verdicts)?),
+            None => None,
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            source,
+            dev,
+            grab,
+            pressed: HashSet::new(),
+            switches: AttributeSet::new(),
+            dropping: false,
+            axes,
+        })
+    }
+
+    /// Passes on the LEDs the desktop set on the virtual device of a grabbed device, as it cannot
+    /// set them on the grabbed device itself.
+    fn forward_leds(&mut self) {
+        let Some(grab) = &mut self.grab else {
+            return;
+        };
+        let path = self.path.to_string_lossy();
+        let leds = match grab.output.fetch_events() {
+            Ok(events) => events
+                .into_iter()
+                .filter(|event| event.event_type() == EventType::LED)
+                .collect::<Vec<_>>(),
+            Err(err) => {
+                log::warn!("Failed to read the LEDs set for {path}: {err}");
+                return;
+            }
+        };
+        if leds.is_empty() {
+            return;
+        }
+        if let Err(err) = self.dev.send_events(&leds) {
+            log::warn!("Failed to set the LEDs of {path}: {err}");
+        }
+    }
+
+    /// Reads the pending events, returning whether some were lost, in which case the held keys
+    /// need to be synchronized again.
+    fn read(
+        &mut self,
+        sender: &Sender<Event>,
+        held: &mut Held,
+        stats: &Stats,
+    ) -> Result<bool, Error> {
+        let pressed = &mut self.pressed;
+        let switches = &mut self.switches;
+        let dropping = &mut self.dropping;
+        let mut dropped = false;
+        let events = self
+            .dev
+            .fetch_events()
+            .map_err(|_| Error::Disconnected)?
+            // the rest of a report with dropped events is incomplete, see the evdev kernel docs
+            .filter(|event| match event.kind() {
+                InputEventKind::Synchronization(Synchronization::SYN_DROPPED) => {
+                    *dropping = true;
+                    dropped = true;
+                    false
+                }
+                InputEventKind::Synchronization(Synchronization::SYN_REPORT) if *dropping => {
+                    *dropping = false;
+                    false
                 }
-                Err(TrySendError::Disconnected(_)) => {
-                    return Err(Error::Hangup);
+                _ => !*dropping,
+            })
+            .inspect(|event| match (event.kind(), event.value()) {
+                (InputEventKind::Key(keycode), PRESSED) => {
+                    pressed.insert(keycode);
                 }
+                (InputEventKind::Key(keycode), RELEASED) => {
+                    pressed.remove(&keycode);
+                }
+                (InputEventKind::Switch(switch), SWITCH_ON) => switches.insert(switch),
+                (InputEventKind::Switch(switch), SWITCH_OFF) => switches.remove(switch),
                 _ => {}
+            });
+        // grabbing waits for the chord handler on every press, so it cannot overflow
+        let overflowed = match &mut self.grab {
+            Some(grab) => grab
+                .filter(events, &self.source, sender, held, &mut self.axes)
+                .map(|_| false)?,
+            None => poll_passive(events, &self.source, sender, held, &mut self.axes)?,
+        };
+        let path = self.path.to_string_lossy();
+        if dropped {
+            let count = stats.syn_dropped.fetch_add(1, Ordering::Relaxed) + 1;
+            log::warn!("The kernel dropped events of {path} ({count} times so far)");
+        }
+        if overflowed {
+            let count = stats.overflows.fetch_add(1, Ordering::Relaxed) + 1;
+            log::warn!("The chord handler fell behind on {path} ({count} times so far)");
+        }
+        Ok(dropped || overflowed)
+    }
+}
+
+/// Counts the devices of each source holding each key, so that the devices of a source look like
+/// one keyboard.
+#[derive(Default)]
+struct Held(HashMap<(Source, Key), usize>);
+
+impl Held {
+    /// Returns whether no other device of the source was already holding the key.
+    fn press(&mut self, source: &Source, key: Key) -> bool {
+        let count = self.0.entry((source.clone(), key)).or_default();
+        *count += 1;
+        *count == 1
+    }
+
+    /// Returns whether no other device of the source is still holding the key.
+    fn release(&mut self, source: &Source, key: Key) -> bool {
+        let held = (source.clone(), key);
+        match self.0.get_mut(&held) {
+            Some(count) if *count > 1 => {
+                *count -= 1;
+                false
+            }
+            _ => {
+                self.0.remove(&held);
+                true
+            }
+        }
+    }
+}
+
+/// The range of each axis of a device, to report positions in percent, and the last position
+/// reported so that unchanged ones are not sent again.
+#[derive(Default)]
+struct Axes(HashMap<Axis, AxisRange>);
+
+struct AxisRange {
+    minimum: i32,
+    maximum: i32,
+    percent: Option<i32>,
+}
+
+impl Axes {
+    fn new(dev: &RawDevice) -> Result<Self, Error> {
+        let Some(supported) = dev.supported_absolute_axes() else {
+            return Ok(Self::default());
+        };
+        let info = dev.get_abs_state()?;
+        let ranges = supported.iter().filter_map(|axis| {
+            let info = info[axis.0 as usize];
+            let range = AxisRange {
+                minimum: info.minimum,
+                maximum: info.maximum,
+                percent: None,
+            };
+            Some((map_axis(axis)?, range))
+        });
+        Ok(Self(ranges.collect()))
+    }
+
+    /// The event for an axis that moved to `value`, unless its position in percent did not change.
+    fn moved(
+        &mut self,
+        axis: AbsoluteAxisType,
+        value: i32,
+        source: &Source,
+        at: Instant,
+    ) -> Option<Event> {
+        let axis = map_axis(axis)?;
+        let range = self.0.get_mut(&axis)?;
+        // wide enough for any range the kernel reports
+        let (value, minimum, maximum) = (
+            i64::from(value),
+            i64::from(range.minimum),
+            i64::from(range.maximum),
+        );
+        let (from, extent) = if axis.is_centered() {
+            // whether reported from 0 to 255 or around zero, each half scaled on its own
+            let center = (minimum + maximum) / 2;
+            let half = if value < center {
+                center - minimum
+            } else {
+                maximum - center
+            };
+            (center, half)
+        } else {
+            (minimum, maximum - minimum)
+        };
+        let percent = (value - from) * 100 / extent.max(1);
+        let percent = percent.clamp(-100, 100) as i32;
+        if range.percent.replace(percent) == Some(percent) {
+            return None;
+        }
+        Some(Event::Axis(axis, source.clone(), percent, at))
+    }
+}
+
+/// Sends the key events on, returning whether the channel overflowed so some had to be dropped.
+fn poll_passive(
+    events: impl Iterator<Item = InputEvent>,
+    source: &Source,
+    sender: &Sender<Event>,
+    held: &mut Held,
+    axes: &mut Axes,
+) -> Result<bool, Error> {
+    let send = |event| match sender.try_send(event) {
+        // the rest is dropped too, the held keys get synchronized instead
+        Err(TrySendError::Full(_)) => Ok(true),
+        Err(TrySendError::Disconnected(_)) => Err(Error::Hangup),
+        _ => Ok(false),
+    };
+    for raw_event in events {
+        let keycode = match raw_event.kind() {
+            InputEventKind::Key(keycode) => keycode,
+            InputEventKind::RelAxis(axis) => {
+                let Some(key) = map_wheel(axis, raw_event.value()) else {
+                    continue;
+                };
+                let at = instant_of(&raw_event);
+                if send(Event::Pressed(key, source.clone(), at))?
+                    || send(Event::Released(key, source.clone(), at))?
+                {
+                    return Ok(true);
+                }
+                continue;
+            }
+            InputEventKind::AbsAxis(axis) => {
+                let at = instant_of(&raw_event);
+                if let Some(event) = axes.moved(axis, raw_event.value(), source, at) {
+                    if send(event)? {
+                        return Ok(true);
+                    }
+                }
+                continue;
+            }
+            InputEventKind::Switch(switch) => {
+                let Some(key) = map_switch(switch) else {
+                    continue;
+                };
+                let at = instant_of(&raw_event);
+                let event = match raw_event.value() {
+                    SWITCH_ON if held.press(source, key) => Event::Pressed(key, source.clone(), at),
+                    SWITCH_OFF if held.release(source, key) => {
+                        Event::Released(key, source.clone(), at)
+                    }
+                    _ => continue,
+                };
+                if send(event)? {
+                    return Ok(true);
+                }
+                continue;
+            }
+            _ => continue,
+        };
+        let key = map_key(keycode);
+        let event = match raw_event.value() {
+            RELEASED => {
+                if !held.release(source, key) {
+                    continue;
+                }
+                Event::Released(key, source.clone(), instant_of(&raw_event))
+            }
+            PRESSED => {
+                if !held.press(source, key) {
+                    continue;
+                }
+                Event::Pressed(key, source.clone(), instant_of(&raw_event))
+            }
+            HELD => {
+                continue;
+            }
+            o => {
+                log::warn!("Unexpected event value `{o}` for key {}", keycode.code());
+                continue;
+            }
+        };
+        if send(event)? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// An exclusively grabbed device, re-emitting everything the chord handler does not consume.
+struct Grab<'a> {
+    output: VirtualDevice,
+    filter: Filter<'a>,
+}
+
+impl<'a> Grab<'a> {
+    fn new(dev: &mut RawDevice, verdicts: &'a Receiver<Verdict>) -> Result<Self, Error> {
+        let mut builder = VirtualDeviceBuilder::new()?;
+        if let Some(keys) = dev.supported_keys() {
+            builder.enable(EventType::KEY, keys.iter().map(|keycode| keycode.code()))?;
+        }
+        if let Some(axes) = dev.supported_relative_axes() {
+            builder.enable(EventType::RELATIVE, axes.iter().map(|axis| axis.0))?;
+        }
+        if let Some(axes) = dev.supported_absolute_axes() {
+            let info = dev.get_abs_state()?;
+            for axis in axes.iter() {
+                builder.absolute_axis(axis.0, info[axis.0 as usize])?;
+            }
+        }
+        if let Some(switches) = dev.supported_switches() {
+            builder.enable(EventType::SWITCH, switches.iter().map(|switch| switch.0))?;
+        }
+        if let Some(misc) = dev.misc_properties() {
+            builder.enable(EventType::MISC, misc.iter().map(|misc| misc.0))?;
+        }
+        // the desktop sets them on the virtual device, see [`Input::forward_leds`]
+        if let Some(leds) = dev.supported_leds() {
+            builder.enable(EventType::LED, leds.iter().map(|led| led.0))?;
+        }
+        let output = builder.build(VIRTUAL_DEVICE_NAME, dev.input_id())?;
+        dev.grab()?;
+        log::debug!("Grabbed device, forwarding through `{VIRTUAL_DEVICE_NAME}`");
+        Ok(Self {
+            output,
+            filter: Filter::new(verdicts),
+        })
+    }
+
+    fn filter(
+        &mut self,
+        events: impl Iterator<Item = InputEvent>,
+        source: &Source,
+        sender: &Sender<Event>,
+        held: &mut Held,
+        axes: &mut Axes,
+    ) -> Result<(), Error> {
+        let forward = self.filter.filter(events, source, sender, held, axes)?;
+        if !forward.is_empty() {
+            self.output.emit(&forward)?;
+        }
+        Ok(())
+    }
+
+    /// Replays the keys held back once their deadline passed.
+    fn expire(&mut self, now: Instant) -> Result<(), Error> {
+        let forward = self.filter.expire(now);
+        if !forward.is_empty() {
+            self.output.emit(&forward)?;
+        }
+        Ok(())
+    }
+
+    /// Brings the virtual device in line with the keys and switches now held on the grabbed one.
+    /// Keys pressed meanwhile are forwarded without asking the chord handler, which only learns
+    /// of them as part of a [`Event::Sync`].
+    fn sync(
+        &mut self,
+        before: &HashSet<EvKey>,
+        now: &HashSet<EvKey>,
+        switches_before: &AttributeSet<SwitchType>,
+        switches_now: &AttributeSet<SwitchType>,
+    ) -> Result<(), Error> {
+        // the chord handler forgets the keys held back on a sync
+        self.filter.settle(Verdict::Forward);
+        self.expire(Instant::now())?;
+        let released = before
+            .difference(now)
+            .filter(|keycode| !self.filter.consumed.remove(keycode))
+            .map(|keycode| InputEvent::new(EventType::KEY, keycode.code(), RELEASED));
+        let pressed = now
+            .difference(before)
+            .map(|keycode| InputEvent::new(EventType::KEY, keycode.code(), PRESSED));
+        let switched_off = switches_before
+            .iter()
+            .filter(|switch| !switches_now.contains(*switch))
+            .map(|switch| InputEvent::new(EventType::SWITCH, switch.0, SWITCH_OFF));
+        let switched_on = switches_now
+            .iter()
+            .filter(|switch| !switches_before.contains(*switch))
+            .map(|switch| InputEvent::new(EventType::SWITCH, switch.0, SWITCH_ON));
+        let events = released
+            .chain(pressed)
+            .chain(switched_off)
+            .chain(switched_on)
+            .collect::<Vec<_>>();
+        if !events.is_empty() {
+            self.output.emit(&events)?;
+        }
+        Ok(())
+    }
+}
+
+/// Decides which events of a grabbed device are re-emitted, asking the chord handler about every
+/// press.
+struct Filter<'a> {
+    verdicts: &'a Receiver<Verdict>,
+    /// Keys whose press was consumed, so their repeats and release are held back too.
+    consumed: HashSet<EvKey>,
+    /// The events to re-emit, including those held back from before.
+    forward: Vec<InputEvent>,
+    /// Where the current report starts in `forward`.
+    report: usize,
+    /// Where the events held back start in `forward`, while a key is held back.
+    held_from: Option<usize>,
+    /// The keys held back, see [`Verdict::Hold`].
+    held_back: HashSet<EvKey>,
+    /// When the keys held back are replayed at the latest.
+    held_back_until: Option<Instant>,
+}
+
+impl<'a> Filter<'a> {
+    fn new(verdicts: &'a Receiver<Verdict>) -> Self {
+        Self {
+            verdicts,
+            consumed: HashSet::new(),
+            forward: Vec::new(),
+            report: 0,
+            held_from: None,
+            held_back: HashSet::new(),
+            held_back_until: None,
+        }
+    }
+
+    /// Reports the events to the chord handler, returning those to re-emit. Keys held back stay
+    /// with the filter, along with everything after them.
+    fn filter(
+        &mut self,
+        events: impl Iterator<Item = InputEvent>,
+        source: &Source,
+        sender: &Sender<Event>,
+        held: &mut Held,
+        axes: &mut Axes,
+    ) -> Result<Vec<InputEvent>, Error> {
+        // the wheel ticks consumed in the current report
+        let mut wheels = Vec::new();
+        self.report = self.forward.len();
+        for raw_event in events {
+            match raw_event.kind() {
+                InputEventKind::Key(keycode) => {
+                    if self.handle_key(keycode, &raw_event, source, sender, held)? {
+                        self.forward.push(raw_event);
+                    }
+                }
+                InputEventKind::RelAxis(axis) => match map_wheel(axis, raw_event.value()) {
+                    Some(key) => {
+                        if self.handle_wheel(key, &raw_event, source, sender)? {
+                            self.forward.push(raw_event);
+                        } else {
+                            wheels.push(key);
+                        }
+                    }
+                    None => self.forward.push(raw_event),
+                },
+                InputEventKind::Switch(switch) => {
+                    if let Some(key) = map_switch(switch) {
+                        self.handle_switch(key, &raw_event, source, sender, held)?;
+                    }
+                    self.forward.push(raw_event);
+                }
+                // axes are always forwarded, chords can only watch them
+                InputEventKind::AbsAxis(axis) => {
+                    let at = instant_of(&raw_event);
+                    if let Some(event) = axes.moved(axis, raw_event.value(), source, at) {
+                        sender.send(event).map_err(|_| Error::Hangup)?;
+                    }
+                    self.forward.push(raw_event);
+                }
+                InputEventKind::Synchronization(Synchronization::SYN_REPORT) => {
+                    drop_hi_res_wheels(&mut self.forward, self.report, &wheels);
+                    wheels.clear();
+                    self.report = self.forward.len();
+                }
+                // the virtual device terminates each batch with its own report
+                InputEventKind::Synchronization(_) => {}
+                _ => self.forward.push(raw_event),
+            }
+        }
+        drop_hi_res_wheels(&mut self.forward, self.report, &wheels);
+        Ok(self.take_forward())
+    }
+
+    /// Takes the events to re-emit, leaving those held back.
+    fn take_forward(&mut self) -> Vec<InputEvent> {
+        match self.held_from {
+            Some(held_from) => {
+                self.held_from = Some(0);
+                let held_back = self.forward.split_off(held_from);
+                mem::replace(&mut self.forward, held_back)
+            }
+            None => mem::take(&mut self.forward),
+        }
+    }
+
+    /// Replays the keys held back once their deadline passed, returning the events to re-emit.
+    fn expire(&mut self, now: Instant) -> Vec<InputEvent> {
+        if self.held_back_until.is_some_and(|until| until <= now) {
+            self.settle(Verdict::Forward);
+        }
+        self.take_forward()
+    }
+
+    /// Applies the verdict on a press to the keys held back before it, or holds them back for
+    /// longer.
+    fn settle(&mut self, verdict: Verdict) {
+        match (verdict, self.held_from) {
+            (Verdict::Hold(until), _) => {
+                self.held_from.get_or_insert(self.forward.len());
+                self.held_back_until = Some(until);
+                return;
+            }
+            (Verdict::Consume, Some(held_from)) => {
+                let held_back = &self.held_back;
+                let is_press = |event: &InputEvent| match event.kind() {
+                    InputEventKind::Key(keycode) => {
+                        event.value() == PRESSED && held_back.contains(&keycode)
+                    }
+                    _ => false,
+                };
+                // the current report may start among the events held back
+                let report = self.report.max(held_from);
+                let before_report = self.forward[held_from..report]
+                    .iter()
+                    .filter(|event| is_press(event))
+                    .count();
+                let mut index = 0;
+                self.forward.retain(|event| {
+                    index += 1;
+                    index <= held_from || !is_press(event)
+                });
+                self.report -= before_report;
+                self.consumed.extend(self.held_back.drain());
+            }
+            _ => {}
+        }
+        self.held_from = None;
+        self.held_back.clear();
+        self.held_back_until = None;
+    }
+
+    /// Reports a wheel movement to the chord handler as a momentary key, returning whether it
+    /// should be re-emitted.
+    fn handle_wheel(
+        &mut self,
+        key: Key,
+        raw_event: &InputEvent,
+        source: &Source,
+        sender: &Sender<Event>,
+    ) -> Result<bool, Error> {
+        let at = instant_of(raw_event);
+        sender
+            .send(Event::Pressed(key, source.clone(), at))
+            .map_err(|_| Error::Hangup)?;
+        let verdict = self.verdicts.recv().map_err(|_| Error::Hangup)?;
+        sender
+            .send(Event::Released(key, source.clone(), at))
+            .map_err(|_| Error::Hangup)?;
+        self.settle(verdict);
+        Ok(verdict != Verdict::Consume)
+    }
+
+    /// Reports a switch to the chord handler like a key. Switches describe the state of the
+    /// machine rather than input, so they are re-emitted whatever the verdict.
+    fn handle_switch(
+        &mut self,
+        key: Key,
+        raw_event: &InputEvent,
+        source: &Source,
+        sender: &Sender<Event>,
+        held: &mut Held,
+    ) -> Result<(), Error> {
+        let at = instant_of(raw_event);
+        match raw_event.value() {
+            SWITCH_ON if held.press(source, key) => {
+                sender
+                    .send(Event::Pressed(key, source.clone(), at))
+                    .map_err(|_| Error::Hangup)?;
+                let verdict = self.verdicts.recv().map_err(|_| Error::Hangup)?;
+                self.settle(verdict);
+            }
+            SWITCH_OFF if held.release(source, key) => {
+                sender
+                    .send(Event::Released(key, source.clone(), at))
+                    .map_err(|_| Error::Hangup)?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Reports the key to the chord handler, returning whether it should be re-emitted.
+    fn handle_key(
+        &mut self,
+        keycode: EvKey,
+        raw_event: &InputEvent,
+        source: &Source,
+        sender: &Sender<Event>,
+        held: &mut Held,
+    ) -> Result<bool, Error> {
+        let key = map_key(keycode);
+        let at = instant_of(raw_event);
+        // the chord handler does the same when it learns of a later press
+        if self.held_back_until.is_some_and(|until| until <= at)
+            || (raw_event.value() == RELEASED && self.held_back.contains(&keycode))
+        {
+            self.settle(Verdict::Forward);
+        }
+        match raw_event.value() {
+            // another device of the source already holds the key, so the chord handler saw it go down
+            PRESSED if !held.press(source, key) => Ok(true),
+            PRESSED => {
+                sender
+                    .send(Event::Pressed(key, source.clone(), at))
+                    .map_err(|_| Error::Hangup)?;
+                let verdict = self.verdicts.recv().map_err(|_| Error::Hangup)?;
+                self.settle(verdict);
+                match verdict {
+                    Verdict::Forward => Ok(true),
+                    Verdict::Consume => {
+                        self.consumed.insert(keycode);
+                        Ok(false)
+                    }
+                    Verdict::Hold(_) => {
+                        self.held_back.insert(keycode);
+                        Ok(true)
+                    }
+                }
+            }
+            RELEASED => {
+                if held.release(source, key) {
+                    sender
+                        .send(Event::Released(key, source.clone(), at))
+                        .map_err(|_| Error::Hangup)?;
+                }
+                Ok(!self.consumed.remove(&keycode))
+            }
+            // repeats of a key held back would reach the desktop before its press
+            HELD => Ok(!self.consumed.contains(&keycode) && !self.held_back.contains(&keycode)),
+            o => {
+                log::warn!("Unexpected event value `{o}` for key {}", keycode.code());
+                Ok(true)
+            }
+        }
+    }
+}
+
+/// The names of the keys, switches and wheel movements of the device that chords can use, leaving
+/// out codes without a name.
+fn usable_keys(dev: &RawDevice) -> Vec<String> {
+    let keys = dev
+        .supported_keys()
+        .into_iter()
+        .flat_map(|keys| keys.iter());
+    let keys = keys.map(map_key).filter(|key| !matches!(key, Key::Raw(_)));
+    let switches = dev
+        .supported_switches()
+        .into_iter()
+        .flat_map(|switches| switches.iter());
+    let axes = dev
+        .supported_relative_axes()
+        .into_iter()
+        .flat_map(|axes| axes.iter());
+    let wheels = axes.flat_map(|axis| [map_wheel(axis, 1), map_wheel(axis, -1)]);
+    keys.chain(switches.filter_map(map_switch))
+        .chain(wheels.flatten())
+        .map(|key| key.name())
+        .collect()
+}
+
+/// Describes every `/dev/input/event*` device, including those we may not open.
+pub fn list_devices() -> io::Result<Vec<DeviceInfo>> {
+    let by_id = symlinks(&Path::new(DEVICE_DIR).join("by-id"));
+    let by_path = symlinks(&Path::new(DEVICE_DIR).join("by-path"));
+    let mut paths = fs::read_dir(DEVICE_DIR)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("event"))
+        })
+        .collect::<Vec<_>>();
+    // event10 after event9
+    paths.sort_by_key(|path| (path.as_os_str().len(), path.clone()));
+
+    Ok(paths
+        .into_iter()
+        .map(|path| {
+            let links = |all: &[(PathBuf, PathBuf)]| {
+                all.iter()
+                    .filter(|(_, target)| *target == path)
+                    .map(|(link, _)| link.clone())
+                    .collect()
+            };
+            let (by_id, by_path) = (links(&by_id), links(&by_path));
+            match RawDevice::open(&path) {
+                Ok(dev) => DeviceInfo {
+                    name: dev.name().map(str::to_string),
+                    phys: dev.physical_path().map(str::to_string),
+                    uniq: dev.unique_name().map(str::to_string),
+                    vendor: Some(dev.input_id().vendor()),
+                    product: Some(dev.input_id().product()),
+                    version: Some(dev.input_id().version()),
+                    by_id,
+                    by_path,
+                    keys: Some(usable_keys(&dev)),
+                    error: None,
+                    path,
+                },
+                // sysfs is world readable, so describe the device from there instead
+                Err(err) => {
+                    let sys = Path::new("/sys/class/input")
+                        .join(path.file_name().unwrap_or_default())
+                        .join("device");
+                    let read = |attribute: &str| {
+                        fs::read_to_string(sys.join(attribute))
+                            .ok()
+                            .map(|value| value.trim().to_string())
+                            .filter(|value| !value.is_empty())
+                    };
+                    let id = |attribute: &str| {
+                        read(&format!("id/{attribute}"))
+                            .and_then(|value| u16::from_str_radix(&value, 16).ok())
+                    };
+                    DeviceInfo {
+                        name: read("name"),
+                        phys: read("phys"),
+                        uniq: read("uniq"),
+                        vendor: id("vendor"),
+                        product: id("product"),
+                        version: id("version"),
+                        by_id,
+                        by_path,
+                        keys: None,
+                        error: Some(err.to_string()),
+                        path,
+                    }
+                }
             }
+        })
+        .collect())
+}
+
+/// Lists the symlinks in `dir` along with the canonical paths they point to.
+fn symlinks(dir: &Path) -> Vec<(PathBuf, PathBuf)> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut links = entries
+        .filter_map(|entry| {
+            let link = entry.ok()?.path();
+            let target = fs::canonicalize(&link).ok()?;
+            Some((link, target))
+        })
+        .collect::<Vec<_>>();
+    links.sort();
+    links
+}
+
+/// Waits until one of `fds` is readable, or until `timeout_millis` passed (unless negative).
+/// Returns which of them became readable.
+fn wait_readable(fds: &[RawFd], timeout_millis: i32) -> Result<Vec<bool>, Error> {
+    let mut poll_fds = fds
+        .iter()
+        .map(|fd| PollFd::new(*fd, PollFlags::POLLIN))
+        .collect::<Vec<_>>();
+    loop {
+        match poll(&mut poll_fds, timeout_millis) {
+            Ok(_) => break,
+            Err(Errno::EINTR) => continue,
+            Err(errno) => return Err(Error::Io(errno.into())),
         }
     }
+    Ok(poll_fds
+        .iter()
+        .map(|poll_fd| poll_fd.revents().is_some_and(|revents| !revents.is_empty()))
+        .collect())
+}
+
+/// Converts the kernel timestamp of an event to an [`Instant`], so timing stays accurate even
+/// when events are read late.
+fn instant_of(event: &InputEvent) -> Instant {
+    let now = Instant::now();
+    SystemTime::now()
+        .duration_since(event.timestamp())
+        .ok()
+        .and_then(|age| now.checked_sub(age))
+        .unwrap_or(now)
 }
 
 impl From<io::Error> for Error {
     fn from(value: io::Error) -> Self {
         match value.kind() {
             ErrorKind::NotFound => Self::NotFound,
+            ErrorKind::PermissionDenied => Self::PermissionDenied,
             _ => Self::Io(value),
         }
     }
@@ -112,8 +1278,10 @@ impl From<io::Error> for Error {
 #[derive(Debug)]
 enum Error {
     NotFound,
+    PermissionDenied,
     Disconnected,
     Hangup,
+    Stopped,
     Io(io::Error),
 }
 
@@ -121,8 +1289,10 @@ impl Display for Error {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             Error::NotFound => write!(f, "Device file not found"),
+            Error::PermissionDenied => write!(f, "Not allowed to open the device"),
             Error::Disconnected => write!(f, "The device was disconnected"),
             Error::Hangup => write!(f, "The receiving thread hung up"),
+            Error::Stopped => write!(f, "The backend was asked to stop"),
             Error::Io(io) => write!(f, "{io}"),
         }
     }
@@ -131,246 +1301,265 @@ impl Display for Error {
 #[derive(Debug)]
 enum Never {}
 
-fn map_key(input: EvKey) -> Option<Key> {
-    Some(match input {
-        EvKey::KEY_ESC => Key::Esc,
-        EvKey::KEY_F1 => Key::F1,
-        EvKey::KEY_F2 => Key::F2,
-        EvKey::KEY_F3 => Key::F3,
-        EvKey::KEY_F4 => Key::F4,
-        EvKey::KEY_F5 => Key::F5,
-        EvKey::KEY_F6 => Key::F6,
-        EvKey::KEY_F7 => Key::F7,
-        EvKey::KEY_F8 => Key::F8,
-        EvKey::KEY_F9 => Key::F9,
-        EvKey::KEY_F10 => Key::F10,
-        EvKey::KEY_F11 => Key::F11,
-        EvKey::KEY_F12 => Key::F12,
-        EvKey::KEY_F13 => Key::F13,
-        EvKey::KEY_F14 => Key::F14,
-        EvKey::KEY_F15 => Key::F15,
-        EvKey::KEY_F16 => Key::F16,
-        EvKey::KEY_F17 => Key::F17,
-        EvKey::KEY_F18 => Key::F18,
-        EvKey::KEY_F19 => Key::F19,
-        EvKey::KEY_F20 => Key::F20,
-        EvKey::KEY_F21 => Key::F21,
-        EvKey::KEY_F22 => Key::F22,
-        EvKey::KEY_F23 => Key::F23,
-        EvKey::KEY_F24 => Key::F24,
-        EvKey::KEY_LEFTCTRL => Key::LeftCtrl,
-        EvKey::KEY_RIGHTCTRL => Key::RightCtrl,
-        EvKey::KEY_LEFTALT => Key::LeftAlt,
-        EvKey::KEY_RIGHTALT => Key::RightAlt,
-        EvKey::KEY_LEFTSHIFT => Key::LeftShift,
-        EvKey::KEY_RIGHTSHIFT => Key::RightShift,
-        EvKey::KEY_LEFTMETA => Key::LeftMeta,
-        EvKey::KEY_RIGHTMETA => Key::RightMeta,
-        EvKey::KEY_1 => Key::N1,
-        EvKey::KEY_2 => Key::N2,
-        EvKey::KEY_3 => Key::N3,
-        EvKey::KEY_4 => Key::N4,
-        EvKey::KEY_5 => Key::N5,
-        EvKey::KEY_6 => Key::N6,
-        EvKey::KEY_7 => Key::N7,
-        EvKey::KEY_8 => Key::N8,
-        EvKey::KEY_9 => Key::N9,
-        EvKey::KEY_0 => Key::N0,
-        EvKey::KEY_Q => Key::Q,
-        EvKey::KEY_W => Key::W,
-        EvKey::KEY_E => Key::E,
-        EvKey::KEY_R => Key::R,
-        EvKey::KEY_T => Key::T,
-        EvKey::KEY_Y => Key::Y,
-        EvKey::KEY_U => Key::U,
-        EvKey::KEY_I => Key::I,
-        EvKey::KEY_O => Key::O,
-        EvKey::KEY_P => Key::P,
-        EvKey::KEY_A => Key::A,
-        EvKey::KEY_S => Key::S,
-        EvKey::KEY_D => Key::D,
-        EvKey::KEY_F => Key::F,
-        EvKey::KEY_G => Key::G,
-        EvKey::KEY_H => Key::H,
-        EvKey::KEY_J => Key::J,
-        EvKey::KEY_K => Key::K,
-        EvKey::KEY_L => Key::L,
-        EvKey::KEY_Z => Key::Z,
-        EvKey::KEY_X => Key::X,
-        EvKey::KEY_C => Key::C,
-        EvKey::KEY_V => Key::V,
-        EvKey::KEY_B => Key::B,
-        EvKey::KEY_N => Key::N,
-        EvKey::KEY_M => Key::M,
-        EvKey::KEY_MINUS => Key::Minus,
-        EvKey::KEY_EQUAL => Key::Equal,
-        EvKey::KEY_BACKSPACE => Key::Backspace,
-        EvKey::KEY_TAB => Key::Tab,
-        EvKey::KEY_LEFTBRACE => Key::LeftBracket,
-        EvKey::KEY_RIGHTBRACE => Key::RightBracket,
-        EvKey::KEY_ENTER => Key::Enter,
-        EvKey::KEY_SEMICOLON => Key::Semicolon,
-        EvKey::KEY_APOSTROPHE => Key::Apostrophe,
-        EvKey::KEY_GRAVE => Key::Grave,
-        EvKey::KEY_BACKSLASH => Key::Backslash,
-        EvKey::KEY_COMMA => Key::Comma,
-        EvKey::KEY_DOT => Key::Dot,
-        EvKey::KEY_SLASH => Key::Slash,
-        EvKey::KEY_SPACE => Key::Space,
-        EvKey::KEY_CAPSLOCK => Key::CapsLock,
-        EvKey::KEY_NUMLOCK => Key::NumLock,
-        EvKey::KEY_SCROLLLOCK => Key::ScrollLock,
-        EvKey::KEY_KP0 => Key::KP0,
-        EvKey::KEY_KP1 => Key::KP1,
-        EvKey::KEY_KP2 => Key::KP2,
-        EvKey::KEY_KP3 => Key::KP3,
-        EvKey::KEY_KP4 => Key::KP4,
-        EvKey::KEY_KP5 => Key::KP5,
-        EvKey::KEY_KP6 => Key::KP6,
-        EvKey::KEY_KP7 => Key::KP7,
-        EvKey::KEY_KP8 => Key::KP8,
-        EvKey::KEY_KP9 => Key::KP9,
-        EvKey::KEY_KPENTER => Key::KPEnter,
-        EvKey::KEY_KPDOT => Key::KPDot,
-        EvKey::KEY_KPMINUS => Key::KPMinus,
-        EvKey::KEY_KPPLUS => Key::KPPlus,
-        EvKey::KEY_KPASTERISK => Key::KPAsterisk,
-        EvKey::KEY_KPSLASH => Key::KPSlash,
-        EvKey::KEY_KPJPCOMMA => Key::KPJPComma,
-        EvKey::KEY_ZENKAKUHANKAKU => Key::Zenkakuhankaku,
-        EvKey::KEY_RO => Key::Ro,
-        EvKey::KEY_KATAKANA => Key::Katakana,
-        EvKey::KEY_HIRAGANA => Key::Hiragana,
-        EvKey::KEY_HENKAN => Key::Henkan,
-        EvKey::KEY_KATAKANAHIRAGANA => Key::Katakanahiragana,
-        EvKey::KEY_MUHENKAN => Key::Muhenkan,
-        EvKey::KEY_SYSRQ => Key::SysRq,
-        EvKey::KEY_LINEFEED => Key::Linefeed,
-        EvKey::KEY_HOME => Key::Home,
-        EvKey::KEY_UP => Key::Up,
-        EvKey::KEY_PAGEUP => Key::Pageup,
-        EvKey::KEY_LEFT => Key::Left,
-        EvKey::KEY_RIGHT => Key::Right,
-        EvKey::KEY_END => Key::End,
-        EvKey::KEY_DOWN => Key::Down,
-        EvKey::KEY_PAGEDOWN => Key::Pagedown,
-        EvKey::KEY_INSERT => Key::Insert,
-        EvKey::KEY_DELETE => Key::Delete,
-        EvKey::KEY_MACRO => Key::Macro,
-        EvKey::KEY_MUTE => Key::Mute,
-        EvKey::KEY_VOLUMEDOWN => Key::VolumeDown,
-        EvKey::KEY_VOLUMEUP => Key::VolumeUp,
-        EvKey::KEY_POWER => Key::Power,
-        EvKey::KEY_KPEQUAL => Key::KPEqual,
-        EvKey::KEY_KPPLUSMINUS => Key::KPPlusMinus,
-        EvKey::KEY_PAUSE => Key::Pause,
-        EvKey::KEY_SCALE => Key::Scale,
-        EvKey::KEY_KPCOMMA => Key::KPComma,
-        EvKey::KEY_HANGEUL => Key::Hangeul,
-        EvKey::KEY_HANJA => Key::Hanja,
-        EvKey::KEY_YEN => Key::Yen,
-        EvKey::KEY_COMPOSE => Key::Compose,
-        EvKey::KEY_AGAIN => Key::Again,
-        EvKey::KEY_PROPS => Key::Props,
-        EvKey::KEY_UNDO => Key::Undo,
-        EvKey::KEY_FRONT => Key::Front,
-        EvKey::KEY_COPY => Key::Copy,
-        EvKey::KEY_OPEN => Key::Open,
-        EvKey::KEY_PASTE => Key::Paste,
-        EvKey::KEY_FIND => Key::Find,
-        EvKey::KEY_CUT => Key::Cut,
-        EvKey::KEY_HELP => Key::Help,
-        EvKey::KEY_MENU => Key::Menu,
-        EvKey::KEY_CALC => Key::Calc,
-        EvKey::KEY_SETUP => Key::Setup,
-        EvKey::KEY_SLEEP => Key::Sleep,
-        EvKey::KEY_WAKEUP => Key::Wakeup,
-        EvKey::KEY_FILE => Key::File,
-        EvKey::KEY_SENDFILE => Key::SendFile,
-        EvKey::KEY_DELETEFILE => Key::DeleteFile,
-        EvKey::KEY_XFER => Key::Xfer,
-        EvKey::KEY_PROG1 => Key::Prog1,
-        EvKey::KEY_PROG2 => Key::Prog2,
-        EvKey::KEY_WWW => Key::WWW,
-        EvKey::KEY_MSDOS => Key::MSDOS,
-        EvKey::KEY_COFFEE => Key::ScreenLock,
-        EvKey::KEY_ROTATE_DISPLAY => Key::RotateDisplay,
-        EvKey::KEY_CYCLEWINDOWS => Key::CycleWindows,
-        EvKey::KEY_MAIL => Key::Mail,
-        EvKey::KEY_BOOKMARKS => Key::Bookmarks,
-        EvKey::KEY_COMPUTER => Key::Computer,
-        EvKey::KEY_BACK => Key::Back,
-        EvKey::KEY_FORWARD => Key::Forward,
-        EvKey::KEY_CLOSECD => Key::CloseCD,
-        EvKey::KEY_EJECTCD => Key::EjectCD,
-        EvKey::KEY_EJECTCLOSECD => Key::EjectCloseCD,
-        EvKey::KEY_NEXTSONG => Key::NextSong,
-        EvKey::KEY_PLAYPAUSE => Key::PlayPause,
-        EvKey::KEY_PREVIOUSSONG => Key::PreviousSong,
-        EvKey::KEY_STOPCD => Key::StopCD,
-        EvKey::KEY_RECORD => Key::Record,
-        EvKey::KEY_REWIND => Key::Rewind,
-        EvKey::KEY_PHONE => Key::Phone,
-        EvKey::KEY_ISO => Key::Iso,
-        EvKey::KEY_CONFIG => Key::Config,
-        EvKey::KEY_HOMEPAGE => Key::Homepage,
-        EvKey::KEY_REFRESH => Key::Refresh,
-        EvKey::KEY_EXIT => Key::Exit,
-        EvKey::KEY_MOVE => Key::Move,
-        EvKey::KEY_EDIT => Key::Edit,
-        EvKey::KEY_SCROLLUP => Key::ScrollUp,
-        EvKey::KEY_SCROLLDOWN => Key::ScrollDown,
-        EvKey::KEY_KPLEFTPAREN => Key::KPLeftParen,
-        EvKey::KEY_KPRIGHTPAREN => Key::KPRightParen,
-        EvKey::KEY_NEW => Key::New,
-        EvKey::KEY_REDO => Key::Redo,
-        EvKey::KEY_PLAYCD => Key::PlayCD,
-        EvKey::KEY_PAUSECD => Key::PauseCD,
-        EvKey::KEY_PROG3 => Key::Prog3,
-        EvKey::KEY_PROG4 => Key::Prog4,
-        EvKey::KEY_DASHBOARD => Key::AllApplications,
-        EvKey::KEY_SUSPEND => Key::Suspend,
-        EvKey::KEY_CLOSE => Key::Close,
-        EvKey::KEY_PLAY => Key::Play,
-        EvKey::KEY_FASTFORWARD => Key::FastForward,
-        EvKey::KEY_BASSBOOST => Key::BassBoost,
-        EvKey::KEY_PRINT => Key::Print,
-        EvKey::KEY_HP => Key::Hp,
-        EvKey::KEY_CAMERA => Key::Camera,
-        EvKey::KEY_SOUND => Key::Sound,
-        EvKey::KEY_QUESTION => Key::Question,
-        EvKey::KEY_EMAIL => Key::Email,
-        EvKey::KEY_CHAT => Key::Chat,
-        EvKey::KEY_SEARCH => Key::Search,
-        EvKey::KEY_CONNECT => Key::Connect,
-        EvKey::KEY_FINANCE => Key::Finance,
-        EvKey::KEY_SPORT => Key::Sport,
-        EvKey::KEY_SHOP => Key::Shop,
-        EvKey::KEY_ALTERASE => Key::AltErase,
-        EvKey::KEY_CANCEL => Key::Cancel,
-        EvKey::KEY_BRIGHTNESSDOWN => Key::BrightnessDown,
-        EvKey::KEY_BRIGHTNESSUP => Key::BrightnessUp,
-        EvKey::KEY_MEDIA => Key::Media,
-        EvKey::KEY_SWITCHVIDEOMODE => Key::SwitchVideoMode,
-        EvKey::KEY_SEND => Key::Send,
-        EvKey::KEY_REPLY => Key::Reply,
-        EvKey::KEY_FORWARDMAIL => Key::ForwardMail,
-        EvKey::KEY_SAVE => Key::Save,
-        EvKey::KEY_DOCUMENTS => Key::Documents,
-        EvKey::KEY_BATTERY => Key::Battery,
-        EvKey::KEY_BLUETOOTH => Key::Bluetooth,
-        EvKey::KEY_WLAN => Key::WLAN,
-        EvKey::KEY_UWB => Key::UWB,
-        EvKey::KEY_VIDEO_NEXT => Key::VideoNext,
-        EvKey::KEY_VIDEO_PREV => Key::VideoPrev,
-        EvKey::KEY_BRIGHTNESS_CYCLE => Key::BrightnessCycle,
-        EvKey::KEY_BRIGHTNESS_AUTO => Key::BrightnessAuto,
-        EvKey::KEY_DISPLAY_OFF => Key::DisplayOff,
-        EvKey::KEY_WWAN => Key::WWAN,
-        EvKey::KEY_RFKILL => Key::RFKill,
-        EvKey::KEY_MICMUTE => Key::MicMute,
-        _ => {
-            return None;
-        }
+/// Names the key, or failing that reports it by its raw code.
+fn map_key(input: EvKey) -> Key {
+    Key::from_code(input.code())
+}
+
+fn map_switch(switch: SwitchType) -> Option<Key> {
+    Key::from_switch(switch.0)
+}
+
+/// The axes chords can use, by the names of the usual gamepad layout.
+fn map_axis(axis: AbsoluteAxisType) -> Option<Axis> {
+    Some(match axis {
+        AbsoluteAxisType::ABS_X => Axis::LeftX,
+        AbsoluteAxisType::ABS_Y => Axis::LeftY,
+        AbsoluteAxisType::ABS_Z => Axis::LeftTrigger,
+        AbsoluteAxisType::ABS_RX => Axis::RightX,
+        AbsoluteAxisType::ABS_RY => Axis::RightY,
+        AbsoluteAxisType::ABS_RZ => Axis::RightTrigger,
+        AbsoluteAxisType::ABS_THROTTLE => Axis::Throttle,
+        AbsoluteAxisType::ABS_RUDDER => Axis::Rudder,
+        AbsoluteAxisType::ABS_GAS => Axis::Gas,
+        AbsoluteAxisType::ABS_BRAKE => Axis::Brake,
+        AbsoluteAxisType::ABS_HAT0X => Axis::HatX,
+        AbsoluteAxisType::ABS_HAT0Y => Axis::HatY,
+        _ => return None,
     })
 }
+
+/// The momentary key a wheel movement stands for, which is pressed and released at once.
+fn map_wheel(axis: RelativeAxisType, value: i32) -> Option<Key> {
+    match (axis, value.signum()) {
+        (RelativeAxisType::REL_WHEEL, 1) => Some(Key::WheelUp),
+        (RelativeAxisType::REL_WHEEL, -1) => Some(Key::WheelDown),
+        (RelativeAxisType::REL_HWHEEL, 1) => Some(Key::HWheelRight),
+        (RelativeAxisType::REL_HWHEEL, -1) => Some(Key::HWheelLeft),
+        // including the high resolution wheel axes, which repeat the same movement in finer steps
+        _ => None,
+    }
+}
+
+/// The wheel key whose movement a high resolution wheel event repeats.
+fn map_hi_res_wheel(axis: RelativeAxisType, value: i32) -> Option<Key> {
+    match axis {
+        RelativeAxisType::REL_WHEEL_HI_RES => map_wheel(RelativeAxisType::REL_WHEEL, value),
+        RelativeAxisType::REL_HWHEEL_HI_RES => map_wheel(RelativeAxisType::REL_HWHEEL, value),
+        _ => None,
+    }
+}
+
+/// Removes the high resolution events repeating the consumed `wheels` from the report starting
+/// at `report` in `forward`, since desktops scroll by those on devices that have them.
+fn drop_hi_res_wheels(forward: &mut Vec<InputEvent>, report: usize, wheels: &[Key]) {
+    if wheels.is_empty() {
+        return;
+    }
+    let rest = forward.split_off(report);
+    forward.extend(rest.into_iter().filter(|event| match event.kind() {
+        InputEventKind::RelAxis(axis) => {
+            !map_hi_res_wheel(axis, event.value()).is_some_and(|key| wheels.contains(&key))
+        }
+        _ => true,
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        backend::{
+            evdev::{Axes, AxisRange, Filter, Held},
+            Event, Verdict,
+        },
+        key::{Axis, Key},
+    };
+    use evdev::{
+        AbsoluteAxisType, EventType, InputEvent, Key as EvKey, RelativeAxisType, Synchronization,
+    };
+    use std::time::{Duration, Instant};
+
+    fn rel(axis: RelativeAxisType, value: i32) -> InputEvent {
+        InputEvent::new(EventType::RELATIVE, axis.0, value)
+    }
+
+    fn report() -> InputEvent {
+        InputEvent::new(EventType::SYNCHRONIZATION, Synchronization::SYN_REPORT.0, 0)
+    }
+
+    #[test]
+    fn filter_drops_hi_res_wheel_of_consumed_ticks() {
+        let (verdict_sender, verdicts) = crossbeam_channel::unbounded();
+        let (sender, events) = crossbeam_channel::unbounded();
+        let mut filter = Filter::new(&verdicts);
+        verdict_sender.send(Verdict::Consume).expect("Sending");
+        verdict_sender.send(Verdict::Forward).expect("Sending");
+        let input = [
+            // the hi-res event can come before the tick it adds up to
+            rel(RelativeAxisType::REL_WHEEL_HI_RES, 120),
+            rel(RelativeAxisType::REL_HWHEEL_HI_RES, 30),
+            rel(RelativeAxisType::REL_WHEEL, 1),
+            rel(RelativeAxisType::REL_X, 5),
+            report(),
+            rel(RelativeAxisType::REL_WHEEL_HI_RES, 60),
+            report(),
+            rel(RelativeAxisType::REL_WHEEL, -1),
+            rel(RelativeAxisType::REL_WHEEL_HI_RES, -120),
+            report(),
+        ];
+        let forward = filter
+            .filter(
+                input.into_iter(),
+                &None,
+                &sender,
+                &mut Held::default(),
+                &mut Axes::default(),
+            )
+            .expect("Filtering");
+
+        let forward = forward
+            .iter()
+            .map(|event| (event.code(), event.value()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            forward,
+            [
+                (RelativeAxisType::REL_HWHEEL_HI_RES.0, 30),
+                (RelativeAxisType::REL_X.0, 5),
+                (RelativeAxisType::REL_WHEEL_HI_RES.0, 60),
+                (RelativeAxisType::REL_WHEEL.0, -1),
+                (RelativeAxisType::REL_WHEEL_HI_RES.0, -120),
+            ]
+        );
+        let pressed = events.try_iter().filter_map(|event| match event {
+            Event::Pressed(key, ..) => Some(key),
+            _ => None,
+        });
+        assert_eq!(pressed.collect::<Vec<_>>(), [Key::WheelUp, Key::WheelDown]);
+    }
+
+    fn key(key: EvKey, value: i32) -> InputEvent {
+        InputEvent::new(EventType::KEY, key.code(), value)
+    }
+
+    /// The keys and values of the key events among `events`.
+    fn keys(events: &[InputEvent]) -> Vec<(u16, i32)> {
+        let keys = events
+            .iter()
+            .filter(|event| event.event_type() == EventType::KEY);
+        keys.map(|event| (event.code(), event.value())).collect()
+    }
+
+    #[test]
+    fn filter_holds_back_keys_until_settled() {
+        let (verdict_sender, verdicts) = crossbeam_channel::unbounded();
+        let (sender, _events) = crossbeam_channel::unbounded();
+        let mut filter = Filter::new(&verdicts);
+        let mut held = Held::default();
+        let mut filter_events = |filter: &mut Filter, events: &[InputEvent]| {
+            let events = events.iter().copied();
+            let forward = filter.filter(events, &None, &sender, &mut held, &mut Axes::default());
+            keys(&forward.expect("Filtering"))
+        };
+        let until = Instant::now() + Duration::from_secs(3600);
+        let (a, s, d) = (
+            EvKey::KEY_A.code(),
+            EvKey::KEY_S.code(),
+            EvKey::KEY_D.code(),
+        );
+
+        // consumed along with the key completing the chord, releases included
+        verdict_sender.send(Verdict::Hold(until)).expect("Sending");
+        assert_eq!(
+            filter_events(&mut filter, &[key(EvKey::KEY_A, 1), report()]),
+            []
+        );
+        verdict_sender.send(Verdict::Consume).expect("Sending");
+        let events = [
+            key(EvKey::KEY_A, 2),
+            key(EvKey::KEY_S, 1),
+            report(),
+            key(EvKey::KEY_A, 0),
+            key(EvKey::KEY_S, 0),
+            report(),
+        ];
+        assert_eq!(filter_events(&mut filter, &events), []);
+
+        // passed on in order once a key not part of the chord goes down
+        verdict_sender.send(Verdict::Hold(until)).expect("Sending");
+        verdict_sender.send(Verdict::Forward).expect("Sending");
+        let events = [
+            key(EvKey::KEY_A, 1),
+            report(),
+            key(EvKey::KEY_D, 1),
+            report(),
+        ];
+        assert_eq!(filter_events(&mut filter, &events), [(a, 1), (d, 1)]);
+        let events = [key(EvKey::KEY_A, 0), key(EvKey::KEY_D, 0), report()];
+        assert_eq!(filter_events(&mut filter, &events), [(a, 0), (d, 0)]);
+
+        // passed on when released
+        verdict_sender.send(Verdict::Hold(until)).expect("Sending");
+        let events = [
+            key(EvKey::KEY_S, 1),
+            report(),
+            key(EvKey::KEY_S, 0),
+            report(),
+        ];
+        assert_eq!(filter_events(&mut filter, &events), [(s, 1), (s, 0)]);
+
+        // or once the window is over
+        verdict_sender.send(Verdict::Hold(until)).expect("Sending");
+        assert_eq!(
+            filter_events(&mut filter, &[key(EvKey::KEY_A, 1), report()]),
+            []
+        );
+        assert_eq!(keys(&filter.expire(Instant::now())), []);
+        assert_eq!(keys(&filter.expire(until)), [(a, 1)]);
+    }
+
+    #[test]
+    fn axes_scale_extreme_ranges() {
+        let range = |minimum, maximum| AxisRange {
+            minimum,
+            maximum,
+            percent: None,
+        };
+        let mut axes = Axes(maplit::hashmap! {
+            Axis::LeftX => range(i32::MIN, i32::MAX),
+            Axis::LeftTrigger => range(0, i32::MAX),
+        });
+        let now = Instant::now();
+        let mut percent = |axis, value| match axes.moved(axis, value, &None, now) {
+            Some(Event::Axis(_, _, percent, _)) => Some(percent),
+            _ => None,
+        };
+
+        assert_eq!(percent(AbsoluteAxisType::ABS_X, i32::MIN), Some(-100));
+        assert_eq!(percent(AbsoluteAxisType::ABS_X, i32::MAX), Some(100));
+        assert_eq!(percent(AbsoluteAxisType::ABS_X, 0), Some(0));
+        assert_eq!(percent(AbsoluteAxisType::ABS_Z, i32::MAX / 3), Some(33));
+        // unchanged positions are not reported again
+        assert_eq!(percent(AbsoluteAxisType::ABS_Z, i32::MAX / 3 + 1), None);
+    }
+
+    #[test]
+    fn axes_center_sticks_without_negative_values() {
+        let mut axes = Axes(maplit::hashmap! {
+            Axis::LeftX => AxisRange {
+                minimum: 0,
+                maximum: 255,
+                percent: None,
+            },
+        });
+        let now = Instant::now();
+        let mut percent = |value| match axes.moved(AbsoluteAxisType::ABS_X, value, &None, now) {
+            Some(Event::Axis(_, _, percent, _)) => Some(percent),
+            _ => None,
+        };
+
+        assert_eq!(percent(0), Some(-100));
+        assert_eq!(percent(128), Some(0));
+        assert_eq!(percent(255), Some(100));
+    }
+}
@@ -1,20 +1,189 @@
-use crate::{config::Backend, key::Key};
+use crate::{
+    config::Backend,
+    key::{Axis, Key},
+};
 use crossbeam_channel::Receiver;
-use std::thread::JoinHandle;
+use serde::Serialize;
+use std::{
+    collections::HashSet,
+    io,
+    io::{Read, Write},
+    net::Shutdown,
+    os::unix::{
+        io::{AsRawFd, RawFd},
+        net::UnixStream,
+    },
+    path::PathBuf,
+    sync::{atomic::AtomicU64, Arc},
+    thread::JoinHandle,
+    time::Instant,
+};
 
 #[cfg(feature = "backend-evdev")]
 mod evdev;
+#[cfg(feature = "backend-evdev")]
+mod uinput;
+
+#[cfg(feature = "backend-evdev")]
+pub use evdev::list_devices;
 
-#[must_use]
-pub fn start_backend(backend: Backend) -> (Receiver<Event>, JoinHandle<()>) {
-    match backend {
+/// Starts the given backend. When `verdicts` is provided the backend grabs its device and waits
+/// for a [`Verdict`] after every [`Event::Pressed`] it sends, re-emitting what is not consumed.
+///
+/// The backend runs until the returned [`Stopper`] is dropped.
+pub fn start_backend(backend: Backend, verdicts: Option<Receiver<Verdict>>) -> io::Result<Started> {
+    let (stopper, stop) = UnixStream::pair()?;
+    let stats = Arc::new(Stats::default());
+    let (recv, handle) = match backend {
         #[cfg(feature = "backend-evdev")]
-        Backend::Evdev { device, retry } => evdev::start(device, retry),
+        Backend::Evdev {
+            devices,
+            inputs,
+            retry,
+        } => {
+            let sources = if inputs.is_empty() {
+                vec![(None, devices)]
+            } else {
+                let named = inputs.into_iter();
+                named
+                    .map(|(name, devices)| (Some(name.into()), devices))
+                    .collect()
+            };
+            evdev::start(sources, retry, verdicts, StopSignal(stop), stats.clone())
+        }
+    };
+    Ok(Started {
+        events: recv,
+        handle,
+        stopper: Stopper { stream: stopper },
+        stats,
+    })
+}
+
+/// A running backend.
+pub struct Started {
+    pub events: Receiver<Event>,
+    pub handle: JoinHandle<()>,
+    pub stopper: Stopper,
+    pub stats: Arc<Stats>,
+}
+
+/// How often a backend lost events and had to synchronize the held keys again.
+#[derive(Serialize, Default, Debug)]
+pub struct Stats {
+    /// Times the kernel dropped events because they were not read fast enough.
+    pub syn_dropped: AtomicU64,
+    /// Times the chord handler fell behind and events did not fit in the channel.
+    pub overflows: AtomicU64,
+}
+
+/// Stops its backend when dropped.
+pub struct Stopper {
+    stream: UnixStream,
+}
+
+impl Stopper {
+    /// Lets another thread ask the backend for an [`Event::Sync`].
+    pub fn resync(&self) -> io::Result<Resync> {
+        Ok(Resync(self.stream.try_clone()?))
+    }
+}
+
+impl Drop for Stopper {
+    fn drop(&mut self) {
+        // reaches the backend even while a `Resync` keeps the socket open
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+/// Asks a backend to report the keys the kernel sees held, through an [`Event::Sync`].
+pub struct Resync(UnixStream);
+
+impl Resync {
+    pub fn request(&self) {
+        if (&self.0).write_all(&[RESYNC]).is_err() {
+            log::debug!("Backend stopped before it could resynchronize");
+        }
+    }
+}
+
+const RESYNC: u8 = b's';
+
+/// What a backend is asked to do through its [`StopSignal`].
+pub enum Signal {
+    Stop,
+    Resync,
+}
+
+/// The backend side of a [`Stopper`]: becomes readable once the backend should stop, or when a
+/// [`Resync`] was requested.
+pub struct StopSignal(UnixStream);
+
+impl StopSignal {
+    /// Reads what was asked, once readable.
+    pub fn read(&self) -> Signal {
+        let mut buf = [0; 64];
+        match (&self.0).read(&mut buf) {
+            Ok(read) if buf[..read].contains(&RESYNC) => Signal::Resync,
+            _ => Signal::Stop,
+        }
     }
 }
 
+impl AsRawFd for StopSignal {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
+
+/// The name of the backend input an event came from, for backends with named `inputs`.
+pub type Source = Option<Arc<str>>;
+
 pub enum Event {
     Stop,
-    Pressed(Key),
-    Released(Key),
+    /// A key went down on a source at the given time.
+    Pressed(Key, Source, Instant),
+    /// A key went up on a source at the given time.
+    Released(Key, Source, Instant),
+    /// The keys held right now, replacing what was known before, after (re)connecting or losing
+    /// events.
+    Sync(HashSet<(Key, Source)>),
+    /// An absolute axis moved to the given position, in percent as in [`Threshold`].
+    ///
+    /// [`Threshold`]: crate::key::Threshold
+    Axis(Axis, Source, i32, Instant),
+}
+
+/// Whether a pressed key should still reach the desktop, for backends that grab their device.
+///
+/// Keys held back stay with the backend, along with everything after them, until the verdict on
+/// a later press settles them too: [`Verdict::Forward`] replays them, [`Verdict::Consume`] drops
+/// them. Releasing one of them, or reaching the deadline, replays them as well.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Verdict {
+    Forward,
+    Consume,
+    /// Holds the key back until the given time at the latest, as a chord with a `window_ms`
+    /// could still consume it.
+    Hold(Instant),
+}
+
+/// An input device a backend could read, as shown by `list-devices`.
+#[derive(Serialize, Debug)]
+pub struct DeviceInfo {
+    pub path: PathBuf,
+    pub name: Option<String>,
+    pub phys: Option<String>,
+    pub uniq: Option<String>,
+    pub vendor: Option<u16>,
+    pub product: Option<u16>,
+    pub version: Option<u16>,
+    /// Stable symlinks to the device, like those in `/dev/input/by-id`.
+    pub by_id: Vec<PathBuf>,
+    pub by_path: Vec<PathBuf>,
+    /// The keys the device reports that chords can name, including switches and wheels, unknown
+    /// if it could not be opened.
+    pub keys: Option<Vec<String>>,
+    /// Why the device could not be opened, usually missing permissions.
+    pub error: Option<String>,
 }
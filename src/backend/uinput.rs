@@ -0,0 +1,170 @@
+//! Virtual devices through uinput. The builder of the evdev crate cannot declare LEDs, which the
+//! desktop needs to show caps lock and the like on a grabbed keyboard.
+
+use evdev::{EventType, InputEvent, InputId};
+use nix::{
+    errno::Errno,
+    ioctl_none, ioctl_write_int, ioctl_write_ptr,
+    libc::{self, c_char, input_absinfo, input_event, uinput_abs_setup, uinput_setup},
+};
+use std::{
+    fs::{File, OpenOptions},
+    io,
+    io::Write,
+    mem,
+    os::unix::{
+        fs::OpenOptionsExt,
+        io::{AsRawFd, RawFd},
+    },
+    slice,
+};
+
+const UINPUT_PATH: &str = "/dev/uinput";
+
+const UINPUT_IOCTL_BASE: u8 = b'U';
+ioctl_none!(ui_dev_create, UINPUT_IOCTL_BASE, 1);
+ioctl_write_ptr!(ui_dev_setup, UINPUT_IOCTL_BASE, 3, uinput_setup);
+ioctl_write_ptr!(ui_abs_setup, UINPUT_IOCTL_BASE, 4, uinput_abs_setup);
+ioctl_write_int!(ui_set_evbit, UINPUT_IOCTL_BASE, 100);
+ioctl_write_int!(ui_set_keybit, UINPUT_IOCTL_BASE, 101);
+ioctl_write_int!(ui_set_relbit, UINPUT_IOCTL_BASE, 102);
+ioctl_write_int!(ui_set_absbit, UINPUT_IOCTL_BASE, 103);
+ioctl_write_int!(ui_set_mscbit, UINPUT_IOCTL_BASE, 104);
+ioctl_write_int!(ui_set_ledbit, UINPUT_IOCTL_BASE, 105);
+ioctl_write_int!(ui_set_swbit, UINPUT_IOCTL_BASE, 109);
+
+/// Declares what a [`VirtualDevice`] can report before creating it.
+pub struct VirtualDeviceBuilder {
+    file: File,
+}
+
+impl VirtualDeviceBuilder {
+    pub fn new() -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NONBLOCK | libc::O_CLOEXEC)
+            .open(UINPUT_PATH)?;
+        Ok(Self { file })
+    }
+
+    /// Lets the device report the given codes of a type of event, which must not be absolute
+    /// axes, see [`VirtualDeviceBuilder::absolute_axis`].
+    pub fn enable(
+        &mut self,
+        event_type: EventType,
+        codes: impl IntoIterator<Item = u16>,
+    ) -> io::Result<()> {
+        let fd = self.file.as_raw_fd();
+        let set_bit = match event_type {
+            EventType::KEY => ui_set_keybit,
+            EventType::RELATIVE => ui_set_relbit,
+            EventType::MISC => ui_set_mscbit,
+            EventType::LED => ui_set_ledbit,
+            EventType::SWITCH => ui_set_swbit,
+            _ => return Err(io::Error::from(io::ErrorKind::Unsupported)),
+        };
+        unsafe { ui_set_evbit(fd, event_type.0.into())? };
+        for code in codes {
+            unsafe { set_bit(fd, code.into())? };
+        }
+        Ok(())
+    }
+
+    /// Lets the device report an absolute axis with the given range.
+    pub fn absolute_axis(&mut self, code: u16, absinfo: input_absinfo) -> io::Result<()> {
+        let fd = self.file.as_raw_fd();
+        unsafe {
+            ui_set_evbit(fd, EventType::ABSOLUTE.0.into())?;
+            ui_set_absbit(fd, code.into())?;
+            ui_abs_setup(fd, &uinput_abs_setup { code, absinfo })?;
+        }
+        Ok(())
+    }
+
+    pub fn build(self, name: &str, id: InputId) -> io::Result<VirtualDevice> {
+        let mut setup = uinput_setup {
+            id: *id.as_ref(),
+            name: [0; libc::UINPUT_MAX_NAME_SIZE],
+            ff_effects_max: 0,
+        };
+        // keeping the last byte for the terminating nul
+        let name = &name.as_bytes()[..name.len().min(libc::UINPUT_MAX_NAME_SIZE - 1)];
+        for (to, from) in setup.name.iter_mut().zip(name) {
+            *to = *from as c_char;
+        }
+        let fd = self.file.as_raw_fd();
+        unsafe {
+            ui_dev_setup(fd, &setup)?;
+            ui_dev_create(fd)?;
+        }
+        Ok(VirtualDevice { file: self.file })
+    }
+}
+
+/// A device created through uinput, destroyed again when dropped.
+pub struct VirtualDevice {
+    file: File,
+}
+
+impl VirtualDevice {
+    /// Reports the events, followed by a `SYN_REPORT`.
+    pub fn emit(&mut self, events: &[InputEvent]) -> io::Result<()> {
+        let report = InputEvent::new(EventType::SYNCHRONIZATION, 0, 0);
+        let events = events
+            .iter()
+            .chain([&report])
+            .map(|event| *event.as_ref())
+            .collect::<Vec<input_event>>();
+        // SAFETY: `input_event` is plain old data, which the kernel reads as such
+        let bytes = unsafe {
+            slice::from_raw_parts(
+                events.as_ptr().cast::<u8>(),
+                mem::size_of_val(events.as_slice()),
+            )
+        };
+        self.file.write_all(bytes)
+    }
+
+    /// Reads the events written to the device by others, such as the LEDs the desktop sets,
+    /// without waiting for any.
+    pub fn fetch_events(&mut self) -> io::Result<Vec<InputEvent>> {
+        const BATCH: usize = 64;
+        let mut buffer = [input_event {
+            time: libc::timeval {
+                tv_sec: 0,
+                tv_usec: 0,
+            },
+            type_: 0,
+            code: 0,
+            value: 0,
+        }; BATCH];
+        let mut events = Vec::new();
+        loop {
+            // SAFETY: the buffer holds `BATCH` events, and the kernel only writes whole ones
+            let read = unsafe {
+                libc::read(
+                    self.file.as_raw_fd(),
+                    buffer.as_mut_ptr().cast(),
+                    mem::size_of_val(&buffer),
+                )
+            };
+            let read = match Errno::result(read) {
+                Ok(read) => read as usize / mem::size_of::<input_event>(),
+                Err(Errno::EAGAIN) => break,
+                Err(errno) => return Err(errno.into()),
+            };
+            events.extend(buffer[..read].iter().copied().map(InputEvent::from));
+            if read < BATCH {
+                break;
+            }
+        }
+        Ok(events)
+    }
+}
+
+impl AsRawFd for VirtualDevice {
+    fn as_raw_fd(&self) -> RawFd {
+        self.file.as_raw_fd()
+    }
+}
@@ -0,0 +1,252 @@
+use crate::{
+    chord::{matches_sequence, state::KeyboardState},
+    config::{Chord, ChordOpts, ConfiguredKey},
+    key::Key,
+};
+use std::{
+    collections::HashSet,
+    time::{Duration, Instant},
+};
+
+/// Tracks the progress of multi-step chords (`steps`) through their sequence of steps.
+#[derive(Default)]
+pub struct Leader {
+    pending: Vec<Pending>,
+}
+
+/// A multi-step chord that has matched up to (but not including) `step`.
+struct Pending {
+    chord: usize,
+    step: usize,
+    deadline: Instant,
+}
+
+impl Leader {
+    /// Forgets the chords that waited for their next step until `now`.
+    pub fn expire(&mut self, now: Instant) {
+        self.pending.retain(|pending| {
+            let expired = pending.deadline <= now;
+            if expired {
+                log::debug!("Multi-step chord #{} timed out", pending.chord + 1);
+            }
+            !expired
+        });
+    }
+
+    /// When the first pending chord times out, if any.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.pending.iter().map(|pending| pending.deadline).min()
+    }
+
+    /// Advances the pending chords with a `key` newly pressed on `source`, already applied to
+    /// `state`.
+    ///
+    /// Returns the index of every chord whose step was matched by this press, along with the keys
+    /// of that step and whether it was the final step (that is, the chord fired). Only chords of
+    /// the given `mode` can be started.
+    pub fn press<'a>(
+        &mut self,
+        state: &KeyboardState,
+        (key, source): (Key, Option<&str>),
+        chords: &'a [Chord],
+        chord_opts: &ChordOpts,
+        mode: Option<&str>,
+        now: Instant,
+    ) -> Vec<(usize, &'a [ConfiguredKey], bool)> {
+        let mut matched = Vec::new();
+        let mut advanced = HashSet::new();
+
+        self.expire(now);
+        self.pending.retain_mut(|pending| {
+            let chord = &chords[pending.chord];
+            let step = &chord.steps[pending.step];
+            if !step.iter().any(|seq_key| seq_key.matches(&key, source)) {
+                log::debug!("Multi-step chord #{} reset by {key}", pending.chord + 1);
+                return false;
+            }
+            let opts = chord_opts.merged(chord.options);
+            if !matches_sequence(state, step, &opts) {
+                // part of the next step, but it is not complete yet
+                return true;
+            }
+            advanced.insert(pending.chord);
+            pending.step += 1;
+            let fired = pending.step == chord.steps.len();
+            matched.push((pending.chord, step.as_slice(), fired));
+            pending.deadline = now + Duration::from_millis(opts.step_timeout_ms);
+            !fired
+        });
+
+        for (index, chord) in chords.iter().enumerate() {
+            let Some(first) = chord.steps.first() else {
+                continue;
+            };
+            if advanced.contains(&index)
+                || chord.mode.as_deref() != mode
+                || !first.iter().any(|seq_key| seq_key.matches(&key, source))
+            {
+                continue;
+            }
+            let opts = chord_opts.merged(chord.options);
+            if !matches_sequence(state, first, &opts) {
+                continue;
+            }
+            // (re)start this chord from its first step
+            self.pending.retain(|pending| pending.chord != index);
+            let fired = chord.steps.len() == 1;
+            matched.push((index, first.as_slice(), fired));
+            if !fired {
+                self.pending.push(Pending {
+                    chord: index,
+                    step: 1,
+                    deadline: now + Duration::from_millis(opts.step_timeout_ms),
+                });
+            }
+        }
+
+        matched
+    }
+
+    pub fn reset(&mut self) {
+        self.pending.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        chord::{leader::Leader, state::KeyboardState},
+        config::{Chord, ChordAction, ChordOpts, ConfiguredKey, Trigger},
+        key::Key,
+    };
+    use std::{
+        str::FromStr,
+        time::{Duration, Instant},
+    };
+
+    fn steps(steps: &[&[&str]]) -> Vec<Vec<ConfiguredKey>> {
+        steps
+            .iter()
+            .map(|step| {
+                step.iter()
+                    .map(|key| ConfiguredKey::from_str(key).expect("Parsing"))
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn leader_chord(action: &str) -> Chord {
+        Chord {
+            name: None,
+            sequence: Vec::new(),
+            steps: steps(&[&["meta", "space"], &["h"]]),
+            trigger: Trigger::Press,
+            mode: None,
+            action: ChordAction::Shell(action.to_owned()),
+            repeat: None,
+            options: None,
+        }
+    }
+
+    /// Presses and releases `keys` in order, returning the actions that fired.
+    fn tap(
+        leader: &mut Leader,
+        state: &mut KeyboardState,
+        chords: &[Chord],
+        keys: &[Key],
+        now: Instant,
+    ) -> Vec<String> {
+        let mut fired = Vec::new();
+        for key in keys {
+            state.press(*key, None, now);
+            let opts = ChordOpts::default();
+            for (index, _, done) in leader.press(state, (*key, None), chords, &opts, None, now) {
+                if let (true, ChordAction::Shell(action)) = (done, &chords[index].action) {
+                    fired.push(action.clone());
+                }
+            }
+        }
+        state.clear();
+        fired
+    }
+
+    #[test]
+    fn leader_fires_after_last_step() {
+        let chords = [leader_chord("help")];
+        let mut leader = Leader::default();
+        let mut state = KeyboardState::default();
+        let now = Instant::now();
+
+        let first = tap(
+            &mut leader,
+            &mut state,
+            &chords,
+            &[Key::LeftMeta, Key::Space],
+            now,
+        );
+        assert!(first.is_empty());
+        let second = tap(&mut leader, &mut state, &chords, &[Key::H], now);
+        assert_eq!(second, vec!["help"]);
+    }
+
+    #[test]
+    fn leader_resets_on_unrelated_key() {
+        let chords = [leader_chord("help")];
+        let mut leader = Leader::default();
+        let mut state = KeyboardState::default();
+        let now = Instant::now();
+
+        tap(
+            &mut leader,
+            &mut state,
+            &chords,
+            &[Key::LeftMeta, Key::Space],
+            now,
+        );
+        tap(&mut leader, &mut state, &chords, &[Key::J], now);
+        let fired = tap(&mut leader, &mut state, &chords, &[Key::H], now);
+        assert!(fired.is_empty());
+    }
+
+    #[test]
+    fn leader_resets_on_timeout() {
+        let chords = [leader_chord("help")];
+        let mut leader = Leader::default();
+        let mut state = KeyboardState::default();
+        let now = Instant::now();
+        let later = now + Duration::from_millis(ChordOpts::default().step_timeout_ms + 1);
+
+        tap(
+            &mut leader,
+            &mut state,
+            &chords,
+            &[Key::LeftMeta, Key::Space],
+            now,
+        );
+        let fired = tap(&mut leader, &mut state, &chords, &[Key::H], later);
+        assert!(fired.is_empty());
+    }
+
+    #[test]
+    fn leader_expires_at_its_deadline() {
+        let chords = [leader_chord("help")];
+        let mut leader = Leader::default();
+        let mut state = KeyboardState::default();
+        let now = Instant::now();
+        let timeout = Duration::from_millis(ChordOpts::default().step_timeout_ms);
+
+        assert_eq!(leader.deadline(), None);
+        tap(
+            &mut leader,
+            &mut state,
+            &chords,
+            &[Key::LeftMeta, Key::Space],
+            now,
+        );
+        assert_eq!(leader.deadline(), Some(now + timeout));
+        leader.expire(now + timeout - Duration::from_millis(1));
+        assert_eq!(leader.deadline(), Some(now + timeout));
+        leader.expire(now + timeout);
+        assert_eq!(leader.deadline(), None);
+    }
+}
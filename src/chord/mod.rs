@@ -0,0 +1,1577 @@
+mod leader;
+mod state;
+mod trigger;
+
+use crate::{
+    backend::{Event, Resync, Source, Verdict},
+    config::{Chord, ChordOpts, ConfiguredKey, Mode, ModeChange, Others},
+    exec,
+    key::{Axis, Key, Threshold},
+};
+use crossbeam_channel::{select, Receiver, Sender};
+use itertools::Itertools;
+use leader::Leader;
+use serde::Serialize;
+use state::KeyboardState;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+use trigger::Triggers;
+
+/// How long to wait for the backend to answer a [`Resync`] request.
+const RESYNC_TIMEOUT: Duration = Duration::from_secs(1);
+
+fn update(state: &mut KeyboardState, event: Event) {
+    match event {
+        Event::Pressed(key, source, at) => {
+            if !state.press(key, source, at) {
+                log::warn!("Duplicate press of {key}, were events dropped?");
+            }
+        }
+        Event::Released(key, source, _) => {
+            if !state.release(&key, &source) {
+                log::warn!("Duplicate release of {key}, were events dropped?");
+            }
+        }
+        Event::Stop => {
+            log::debug!("Device disconnected, clearing cache.");
+            state.clear();
+        }
+        Event::Sync(keys) => {
+            log::debug!("Synchronized held keys: {keys:?}");
+            state.sync(keys, Instant::now());
+        }
+        Event::Axis(..) => {}
+    }
+}
+
+fn match_chords<'a, 'b: 'a, 'c: 'a>(
+    state: &'c KeyboardState,
+    chords: &'a [Chord],
+    chord_opts: &'b ChordOpts,
+    mode: Option<&'b str>,
+) -> impl Iterator<Item = (usize, &'a Chord)> {
+    chords
+        .iter()
+        .enumerate()
+        .filter(move |(_, chord)| chord.mode.as_deref() == mode)
+        // multi-step chords are tracked by the leader state machine instead
+        .filter(|(_, chord)| chord.steps.is_empty())
+        .filter(move |(_, chord)| {
+            // chords that match the state
+            matches_sequence(state, &chord.sequence, &chord_opts.merged(chord.options))
+        })
+        .take_while_inclusive(|(_, chord)| chord_opts.merged(chord.options).passthrough)
+}
+
+fn matches_sequence(state: &KeyboardState, sequence: &[ConfiguredKey], opts: &ChordOpts) -> bool {
+    if !sequence
+        .iter()
+        .all(|seq_key| is_held(state, seq_key) != seq_key.negated())
+    {
+        return false;
+    }
+    if let Some(window) = opts.window_ms {
+        // the keys must have gone down together, not one after the other
+        let pressed = sequence
+            .iter()
+            .filter(|seq_key| !seq_key.negated())
+            .filter_map(|seq_key| {
+                seq_key
+                    .matching()
+                    .filter_map(|key| state.pressed_at(key))
+                    .max()
+            });
+        if let Some((first, last)) = pressed.minmax().into_option() {
+            if last.duration_since(first) > Duration::from_millis(window) {
+                return false;
+            }
+        }
+    }
+    if opts.ordered {
+        // each key must have gone down after the one listed before it, so take for each the
+        // earliest press that did, leaving the later ones to the keys still to come
+        let mut previous = None;
+        for seq_key in sequence.iter().filter(|seq_key| !seq_key.negated()) {
+            let next = seq_key
+                .matching()
+                .flat_map(|key| state.press_orders(key, seq_key.input()))
+                .filter(|order| previous.is_none_or(|previous| previous < *order))
+                .min();
+            if next.is_none() {
+                return false;
+            }
+            previous = next;
+        }
+    }
+    // check every other key in the state is one the policy tolerates
+    state.sources().all(|(state_key, source)| {
+        sequence
+            .iter()
+            .any(|seq_key| seq_key.matches(state_key, source))
+            || match opts.others {
+                Others::Allow => true,
+                Others::Forbid => false,
+                Others::ModifiersOnly => state_key.is_modifier(),
+            }
+    })
+}
+
+/// Whether at least one of the options for the configured key is held.
+fn is_held(state: &KeyboardState, seq_key: &ConfiguredKey) -> bool {
+    seq_key
+        .matching()
+        .any(|seq_key_opt| state.contains(seq_key_opt, seq_key.input()))
+}
+
+/// Whether any key of the sequence is still held.
+fn holds_any_key(state: &KeyboardState, sequence: &[ConfiguredKey]) -> bool {
+    sequence
+        .iter()
+        .any(|seq_key| !seq_key.negated() && is_held(state, seq_key))
+}
+
+/// Whether all keys of the sequence are held, regardless of what else is or is not.
+fn holds_keys(state: &KeyboardState, sequence: &[ConfiguredKey]) -> bool {
+    sequence
+        .iter()
+        .filter(|seq_key| !seq_key.negated())
+        .all(|seq_key| is_held(state, seq_key))
+}
+
+/// Everything an executor's config defines for its chord handler.
+pub struct Bindings {
+    pub chords: Vec<Chord>,
+    pub chord_opts: ChordOpts,
+    pub shell: Option<Vec<String>>,
+    pub modes: HashMap<String, Mode>,
+    /// Held keys are forgotten after this long.
+    pub max_hold: Option<Duration>,
+    /// Whether to have the backend synchronize the held keys before forgetting any.
+    pub max_hold_check: bool,
+    /// How far an axis has to move back past a threshold to release it.
+    pub axis_hysteresis_percent: u8,
+}
+
+/// Requests to a running chord handler.
+pub enum Control {
+    /// Replaces the bindings, keeping the held keys.
+    Reload(Bindings),
+    /// Stops or resumes firing chords.
+    Pause(bool),
+    /// Fires the chord with the given name, replying whether there was one.
+    Trigger(String, Sender<bool>),
+    Status(Sender<Status>),
+}
+
+/// A snapshot of a chord handler, for the control socket.
+#[derive(Serialize, Debug)]
+pub struct Status {
+    pub paused: bool,
+    pub mode: Option<String>,
+    pub held: Vec<String>,
+    pub chords: Vec<ChordStatus>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ChordStatus {
+    pub name: Option<String>,
+    pub keys: String,
+    pub mode: Option<String>,
+}
+
+/// Runs the chords of an executor against the events of its backend, until the backend hangs up.
+pub fn chord_handler(
+    recv: Receiver<Event>,
+    bindings: Bindings,
+    verdicts: Option<Sender<Verdict>>,
+    mut control: Receiver<Control>,
+    resync: Resync,
+) -> JoinHandle<()> {
+    thread::spawn(move || {
+        let mut handler = Handler::new(bindings, verdicts, Some(resync));
+        loop {
+            let timer = handler
+                .deadline()
+                .map(crossbeam_channel::at)
+                .unwrap_or_else(crossbeam_channel::never);
+            select! {
+                recv(recv) -> event => {
+                    let Ok(event) = event else {
+                        break;
+                    };
+                    if !handler.handle(event) {
+                        break;
+                    }
+                }
+                recv(control) -> request => match request {
+                    Ok(request) => handler.control(request),
+                    Err(_) => control = crossbeam_channel::never(),
+                },
+                recv(timer) -> _ => handler.tick(Instant::now()),
+            }
+        }
+    })
+}
+
+/// The chord state of a single executor.
+struct Handler {
+    bindings: Bindings,
+    verdicts: Option<Sender<Verdict>>,
+    keyboard_state: KeyboardState,
+    leader: Leader,
+    triggers: Triggers,
+    /// The mode the executor is in, if any.
+    mode: Option<String>,
+    /// When the current mode times out.
+    mode_deadline: Option<Instant>,
+    paused: bool,
+    resync: Option<Resync>,
+    /// When the backend was asked to synchronize keys held past `max_hold`, until it does.
+    checking: Option<Instant>,
+    /// How many runs of each chord's action have not finished yet, for chords that `repeat`.
+    running: Vec<Arc<AtomicUsize>>,
+    /// The pressed keys the backend holds back, see [`Verdict::Hold`].
+    held_back: Vec<(Key, Source)>,
+    /// When the backend replays the keys held back at the latest.
+    held_back_until: Option<Instant>,
+    /// The chords fired so far, in order, for tests to check.
+    #[cfg(test)]
+    fired: Vec<usize>,
+}
+
+impl Handler {
+    fn new(bindings: Bindings, verdicts: Option<Sender<Verdict>>, resync: Option<Resync>) -> Self {
+        Self {
+            triggers: Triggers::new(&bindings.chords),
+            running: running_counts(&bindings.chords),
+            bindings,
+            verdicts,
+            keyboard_state: KeyboardState::default(),
+            leader: Leader::default(),
+            mode: None,
+            mode_deadline: None,
+            paused: false,
+            resync,
+            checking: None,
+            held_back: Vec::new(),
+            held_back_until: None,
+            #[cfg(test)]
+            fired: Vec::new(),
+        }
+    }
+
+    fn control(&mut self, request: Control) {
+        match request {
+            Control::Reload(bindings) => self.reload(bindings),
+            Control::Pause(paused) => {
+                log::info!("{} chords", if paused { "Pausing" } else { "Resuming" });
+                self.paused = paused;
+                // nothing that happened in between may complete a chord later on
+                self.leader.reset();
+                self.triggers
+                    .reset(&self.bindings.chords, &self.keyboard_state);
+            }
+            Control::Trigger(name, reply) => {
+                let index = self
+                    .bindings
+                    .chords
+                    .iter()
+                    .position(|chord| chord.name.as_ref() == Some(&name));
+                if let Some(index) = index {
+                    log::info!("Triggering chord `{name}`");
+                    self.fire(index, Instant::now());
+                }
+                let _ = reply.send(index.is_some());
+            }
+            Control::Status(reply) => {
+                let _ = reply.send(self.status());
+            }
+        }
+    }
+
+    fn status(&self) -> Status {
+        let chords = self.bindings.chords.iter().map(|chord| {
+            let keys = if chord.steps.is_empty() {
+                join_keys(&chord.sequence)
+            } else {
+                let steps = chord.steps.iter().map(|step| join_keys(step));
+                steps.collect::<Vec<_>>().join(", ")
+            };
+            ChordStatus {
+                name: chord.name.clone(),
+                keys,
+                mode: chord.mode.clone(),
+            }
+        });
+        let mut held: Vec<_> = self
+            .keyboard_state
+            .sources()
+            .map(|(key, source)| match source {
+                Some(input) => format!("{input}:{}", key.name()),
+                None => key.name(),
+            })
+            .collect();
+        held.sort();
+        Status {
+            paused: self.paused,
+            mode: self.mode.clone(),
+            held,
+            chords: chords.collect(),
+        }
+    }
+
+    /// Replaces the bindings, starting over from the default mode.
+    fn reload(&mut self, bindings: Bindings) {
+        log::debug!("Reloading bindings");
+        self.triggers = Triggers::new(&bindings.chords);
+        self.running = running_counts(&bindings.chords);
+        self.bindings = bindings;
+        // held keys should not fire the new chords before they go up
+        self.triggers
+            .reset(&self.bindings.chords, &self.keyboard_state);
+        self.leader.reset();
+        self.mode = None;
+        self.mode_deadline = None;
+    }
+
+    /// Handles an event from the backend, returning false once the backend can no longer be
+    /// answered.
+    fn handle(&mut self, event: Event) -> bool {
+        let (pressed, now) = match event {
+            Event::Pressed(key, ref source, at) => {
+                // the backend replays the keys it held back before passing on a later press
+                if self.held_back_until.is_some_and(|until| until <= at) {
+                    self.release_held_back();
+                }
+                (Some((key, source.clone())), at)
+            }
+            Event::Released(key, ref source, at) => {
+                // as does releasing one of them
+                if self.held_back.contains(&(key, source.clone())) {
+                    self.release_held_back();
+                }
+                (None, at)
+            }
+            Event::Stop => {
+                self.release_held_back();
+                self.leader.reset();
+                self.triggers
+                    .reset(&self.bindings.chords, &self.keyboard_state);
+                (None, Instant::now())
+            }
+            // keys held at a sync were pressed before we could see it, so nothing fires
+            Event::Sync(_) => {
+                self.release_held_back();
+                update(&mut self.keyboard_state, event);
+                self.leader.reset();
+                self.triggers
+                    .reset(&self.bindings.chords, &self.keyboard_state);
+                self.checking = None;
+                return true;
+            }
+            Event::Axis(axis, source, percent, at) => {
+                self.move_axis(axis, source, percent, at);
+                return true;
+            }
+        };
+        update(&mut self.keyboard_state, event);
+        let verdict = self.react(pressed.as_ref(), now);
+        let verdict = match &pressed {
+            Some(pressed) => self.hold_back(pressed, verdict, now),
+            None => verdict,
+        };
+        self.answer(pressed.is_some(), verdict)
+    }
+
+    /// Holds back the pressed key along with those held back before, while a chord with a
+    /// `window_ms` could still consume them all, or settles the keys held back so far.
+    fn hold_back(&mut self, pressed: &(Key, Source), verdict: Verdict, now: Instant) -> Verdict {
+        let verdict = match verdict {
+            _ if self.verdicts.is_none() || self.paused => verdict,
+            // the keys held back are consumed along with the pressed key, but only by a chord
+            // they are all part of
+            Verdict::Consume if !self.consumed_along(pressed) => Verdict::Forward,
+            Verdict::Forward => self
+                .window_deadline(pressed, now)
+                .map_or(verdict, Verdict::Hold),
+            verdict => verdict,
+        };
+        match verdict {
+            Verdict::Hold(until) => {
+                self.held_back.push(pressed.clone());
+                self.held_back_until = Some(until);
+            }
+            Verdict::Forward | Verdict::Consume => self.release_held_back(),
+        }
+        verdict
+    }
+
+    /// Whether a matching chord consuming the pressed key also has every key held back as one
+    /// of its own.
+    fn consumed_along(&self, (key, source): &(Key, Source)) -> bool {
+        let part_of = |chord: &Chord, (key, source): &(Key, Source)| {
+            consumes(
+                &chord.sequence,
+                chord,
+                &self.bindings.chord_opts,
+                key,
+                source.as_deref(),
+            )
+        };
+        let mut matched = match_chords(
+            &self.keyboard_state,
+            &self.bindings.chords,
+            &self.bindings.chord_opts,
+            self.mode.as_deref(),
+        );
+        matched.any(|(_, chord)| {
+            part_of(chord, &(*key, source.clone()))
+                && self.held_back.iter().all(|held| part_of(chord, held))
+        })
+    }
+
+    /// Until when the pressed key is held back, if some chord consuming it with a `window_ms`
+    /// could still be completed in time. All keys of that chord held so far must be held back
+    /// too, or they would reach the desktop anyway.
+    fn window_deadline(&self, pressed: &(Key, Source), now: Instant) -> Option<Instant> {
+        let (key, source) = pressed;
+        // wheels go up at once, switches and axes are not typed
+        if key.is_latched() || Key::WHEELS.contains(key) {
+            return None;
+        }
+        // each device holds back its own keys, and settles them with its own verdicts
+        if self.held_back.iter().any(|(_, held)| held != source) {
+            return None;
+        }
+        let candidates = self.bindings.chords.iter().filter(|chord| {
+            chord.mode.as_deref() == self.mode.as_deref() && chord.steps.is_empty()
+        });
+        candidates
+            .filter_map(|chord| {
+                let opts = self.bindings.chord_opts.merged(chord.options);
+                let window = Duration::from_millis(opts.window_ms.filter(|_| opts.consume)?);
+                let part_of = |(key, source): &(Key, Source)| {
+                    chord.sequence.iter().any(|seq_key| {
+                        !seq_key.negated() && seq_key.matches(key, source.as_deref())
+                    })
+                };
+                if !part_of(pressed) || !self.held_back.iter().all(part_of) {
+                    return None;
+                }
+                let mut first = now;
+                for (key, source) in self.keyboard_state.sources() {
+                    let held = (*key, source.map(Arc::from));
+                    if !part_of(&held) || held == *pressed {
+                        continue;
+                    }
+                    if !self.held_back.contains(&held) {
+                        return None;
+                    }
+                    first = first.min(self.keyboard_state.pressed_at(key)?);
+                }
+                Some(first + window).filter(|until| *until > now)
+            })
+            .max()
+    }
+
+    /// Forgets the keys held back, once the backend settled them.
+    fn release_held_back(&mut self) {
+        self.held_back.clear();
+        self.held_back_until = None;
+    }
+
+    /// Fires the chords matching the keys now held, after `pressed` went down or some key went
+    /// up, returning what should become of the pressed key.
+    fn react(&mut self, pressed: Option<&(Key, Source)>, now: Instant) -> Verdict {
+        let mut verdict = Verdict::Forward;
+        if self.paused {
+            return verdict;
+        }
+        let mut fired = Vec::new();
+        let matched = match_chords(
+            &self.keyboard_state,
+            &self.bindings.chords,
+            &self.bindings.chord_opts,
+            self.mode.as_deref(),
+        )
+        .map(|(index, _)| index)
+        .collect::<Vec<_>>();
+        for &index in &matched {
+            let chord = &self.bindings.chords[index];
+            if pressed.is_some_and(|(key, source)| {
+                consumes(
+                    &chord.sequence,
+                    chord,
+                    &self.bindings.chord_opts,
+                    key,
+                    source.as_deref(),
+                )
+            }) {
+                verdict = Verdict::Consume;
+            }
+        }
+        fired.extend(self.triggers.press(
+            &matched,
+            &self.bindings.chords,
+            &self.bindings.chord_opts,
+            &self.keyboard_state,
+            now,
+        ));
+        // a chord pressed within its window lasts until all of its keys are up, like a steno stroke
+        let strokes = self
+            .bindings
+            .chords
+            .iter()
+            .enumerate()
+            .filter(|(index, chord)| {
+                !matched.contains(index)
+                    && self.triggers.is_active(*index)
+                    && self
+                        .bindings
+                        .chord_opts
+                        .merged(chord.options)
+                        .window_ms
+                        .is_some()
+                    && holds_any_key(&self.keyboard_state, &chord.sequence)
+            });
+        let active = matched
+            .iter()
+            .copied()
+            .chain(strokes.map(|(index, _)| index))
+            .collect::<Vec<_>>();
+        fired.extend(self.triggers.update(
+            &active,
+            &self.bindings.chords,
+            &self.bindings.chord_opts,
+            now,
+        ));
+
+        if let Some((key, source)) = pressed {
+            let steps = self.leader.press(
+                &self.keyboard_state,
+                (*key, source.as_deref()),
+                &self.bindings.chords,
+                &self.bindings.chord_opts,
+                self.mode.as_deref(),
+                now,
+            );
+            for (index, step, done) in steps {
+                if consumes(
+                    step,
+                    &self.bindings.chords[index],
+                    &self.bindings.chord_opts,
+                    key,
+                    source.as_deref(),
+                ) {
+                    verdict = Verdict::Consume;
+                }
+                if done {
+                    fired.push(index);
+                }
+            }
+        }
+
+        for index in fired {
+            self.fire(index, now);
+        }
+        verdict
+    }
+
+    /// Presses and releases the keys standing for positions of an axis that moved to `percent`.
+    /// The backend forwards axes as they are, so these keys need no verdict.
+    fn move_axis(&mut self, axis: Axis, source: Source, percent: i32, at: Instant) {
+        let hysteresis = i32::from(self.bindings.axis_hysteresis_percent);
+        let thresholds = self.thresholds(axis).into_iter();
+        let keys = thresholds
+            .map(|threshold| (Key::Threshold(threshold), threshold, hysteresis))
+            .chain(
+                Threshold::hat(axis)
+                    .into_iter()
+                    .map(|(key, threshold)| (key, threshold, 0)),
+            );
+        let mut changed = Vec::new();
+        for (key, threshold, hysteresis) in keys {
+            let held = self.keyboard_state.contains(&key, source.as_deref());
+            if threshold.holds(percent, held, hysteresis) != held {
+                changed.push((key, held));
+            }
+        }
+        // releases first, so that a hat moving across does not hold both directions at once
+        changed.sort_by_key(|(_, held)| !held);
+        for (key, held) in changed {
+            if held {
+                update(
+                    &mut self.keyboard_state,
+                    Event::Released(key, source.clone(), at),
+                );
+                self.react(None, at);
+            } else {
+                update(
+                    &mut self.keyboard_state,
+                    Event::Pressed(key, source.clone(), at),
+                );
+                self.react(Some(&(key, source.clone())), at);
+            }
+        }
+    }
+
+    /// The thresholds of an axis that chords use.
+    fn thresholds(&self, axis: Axis) -> Vec<Threshold> {
+        let mut thresholds = Vec::new();
+        for chord in &self.bindings.chords {
+            let keys = chord.sequence.iter().chain(chord.steps.iter().flatten());
+            for key in keys.flat_map(ConfiguredKey::matching) {
+                if let Key::Threshold(threshold) = key {
+                    if threshold.axis == axis && !thresholds.contains(threshold) {
+                        thresholds.push(*threshold);
+                    }
+                }
+            }
+        }
+        thresholds
+    }
+
+    /// Tells a grabbing backend what to do with a pressed key, returning false if it hung up.
+    fn answer(&self, pressed: bool, verdict: Verdict) -> bool {
+        if let (Some(verdicts), true) = (&self.verdicts, pressed) {
+            if verdicts.send(verdict).is_err() {
+                log::error!("Backend hung up while waiting for a verdict");
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Handles timers that expired without any event arriving.
+    fn tick(&mut self, now: Instant) {
+        if self.paused {
+            return;
+        }
+        self.leader.expire(now);
+        for index in self
+            .triggers
+            .tick(&self.bindings.chords, &self.bindings.chord_opts, now)
+        {
+            self.fire(index, now);
+        }
+        for index in self.triggers.repeats(&self.bindings.chords, now) {
+            let chord = &self.bindings.chords[index];
+            let max_running = chord.repeat.map_or(usize::MAX, |repeat| repeat.max_running);
+            if self.running[index].load(Ordering::Relaxed) < max_running {
+                self.fire(index, now);
+            } else {
+                log::debug!("Skipping a repeat of chord #{}, still running", index + 1);
+            }
+        }
+        if self.mode_deadline.is_some_and(|deadline| deadline <= now) {
+            log::debug!("Mode timed out");
+            self.switch_mode(None, now);
+        }
+        if self.hold_deadline().is_some_and(|deadline| deadline <= now) {
+            self.expire_held(now);
+        }
+    }
+
+    /// Forgets keys held past `max_hold`, unless the backend should be asked about them first.
+    fn expire_held(&mut self, now: Instant) {
+        let Some(max_hold) = self.bindings.max_hold else {
+            return;
+        };
+        if let (true, None, Some(resync)) =
+            (self.bindings.max_hold_check, self.checking, &self.resync)
+        {
+            log::debug!("Keys held for longer than {max_hold:?}, asking the backend");
+            resync.request();
+            self.checking = Some(now);
+            return;
+        }
+        self.checking = None;
+        let Some(since) = now.checked_sub(max_hold) else {
+            return;
+        };
+        let expired = self.keyboard_state.expire(since);
+        for key in &expired {
+            log::warn!("Forgetting {key}, held for longer than {max_hold:?}");
+        }
+        if !expired.is_empty() {
+            self.leader.reset();
+            self.triggers
+                .reset(&self.bindings.chords, &self.keyboard_state);
+        }
+    }
+
+    /// When keys held too long should be looked at, if ever.
+    fn hold_deadline(&self) -> Option<Instant> {
+        match self.checking {
+            // the backend may be gone, so do not wait for it forever
+            Some(requested) => Some(requested + RESYNC_TIMEOUT),
+            None => Some(self.keyboard_state.oldest()? + self.bindings.max_hold?),
+        }
+    }
+
+    /// The next time [`Handler::tick`] needs to run, if any.
+    fn deadline(&self) -> Option<Instant> {
+        if self.paused {
+            return None;
+        }
+        let triggers = self
+            .triggers
+            .deadline(&self.bindings.chords, &self.bindings.chord_opts);
+        triggers
+            .into_iter()
+            .chain(self.leader.deadline())
+            .chain(self.mode_deadline)
+            .chain(self.hold_deadline())
+            .min()
+    }
+
+    fn fire(&mut self, index: usize, now: Instant) {
+        #[cfg(test)]
+        self.fired.push(index);
+        let chord = &self.bindings.chords[index];
+        let action = &chord.action;
+        let target = match action.mode_change() {
+            None => {
+                let running = chord.repeat.map(|_| self.running[index].clone());
+                exec::exec_counted(action, self.bindings.shell.as_ref(), running);
+                // activity in a mode keeps it from timing out
+                self.mode_deadline = self.mode_timeout(now);
+                return;
+            }
+            Some(ModeChange::Enter(mode)) => Some(mode.clone()),
+            Some(ModeChange::Exit) => None,
+            Some(ModeChange::Toggle(mode)) if self.mode.as_ref() == Some(mode) => None,
+            Some(ModeChange::Toggle(mode)) => Some(mode.clone()),
+        };
+        self.switch_mode(target, now);
+    }
+
+    fn switch_mode(&mut self, target: Option<String>, now: Instant) {
+        if target != self.mode {
+            if let Some(on_exit) = self.current_mode().and_then(|mode| mode.on_exit.as_ref()) {
+                exec::exec_action(on_exit, self.bindings.shell.as_ref());
+            }
+            match &target {
+                Some(mode) => log::info!("Entering mode `{mode}`"),
+                None => log::info!(
+                    "Leaving mode `{}`",
+                    self.mode.as_deref().unwrap_or_default()
+                ),
+            }
+            self.mode = target;
+            if let Some(on_enter) = self.current_mode().and_then(|mode| mode.on_enter.as_ref()) {
+                exec::exec_action(on_enter, self.bindings.shell.as_ref());
+            }
+            // progress made in the previous mode does not carry over
+            self.leader.reset();
+            self.triggers
+                .reset(&self.bindings.chords, &self.keyboard_state);
+        }
+        self.mode_deadline = self.mode_timeout(now);
+    }
+
+    fn current_mode(&self) -> Option<&Mode> {
+        self.bindings.modes.get(self.mode.as_ref()?)
+    }
+
+    /// When the current mode should time out if nothing else happens from `now` on.
+    fn mode_timeout(&self, now: Instant) -> Option<Instant> {
+        let timeout = self.current_mode()?.timeout_ms?;
+        Some(now + Duration::from_millis(timeout))
+    }
+}
+
+fn running_counts(chords: &[Chord]) -> Vec<Arc<AtomicUsize>> {
+    chords.iter().map(|_| Arc::default()).collect()
+}
+
+fn join_keys(keys: &[ConfiguredKey]) -> String {
+    let keys: Vec<_> = keys.iter().map(ToString::to_string).collect();
+    keys.join(" + ")
+}
+
+/// Whether the press of `key` on `source` completing `keys` of `chord` should be kept from the
+/// desktop.
+fn consumes(
+    keys: &[ConfiguredKey],
+    chord: &Chord,
+    chord_opts: &ChordOpts,
+    key: &Key,
+    source: Option<&str>,
+) -> bool {
+    chord_opts.merged(chord.options).consume
+        && keys.iter().any(|seq_key| seq_key.matches(key, source))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        backend::{Event, Verdict},
+        chord::{match_chords, state::KeyboardState, Bindings, Handler},
+        config::{
+            Chord, ChordAction, ChordOpts, ChordOptsChild, ConfiguredKey, Mode, Others, Trigger,
+        },
+        key::{self, Axis, Key, Threshold},
+    };
+    use std::{
+        collections::HashSet,
+        str::FromStr,
+        time::{Duration, Instant},
+    };
+
+    trait VParse {
+        fn parse(self) -> Vec<ConfiguredKey>;
+    }
+
+    impl VParse for Vec<&str> {
+        fn parse(self) -> Vec<ConfiguredKey> {
+            self.into_iter()
+                .map(ConfiguredKey::from_str)
+                .collect::<Result<Vec<ConfiguredKey>, _>>()
+                .expect("Parsing")
+        }
+    }
+
+    /// A chord running `action` once `sequence` is held, outside of any mode.
+    fn chord(sequence: &[&str], action: &str) -> Chord {
+        Chord {
+            name: None,
+            sequence: sequence.to_vec().parse(),
+            steps: Vec::new(),
+            trigger: Trigger::Press,
+            mode: None,
+            action: ChordAction::Shell(action.to_owned()),
+            repeat: None,
+            options: None,
+        }
+    }
+
+    /// A chord entering `mode` once `sequence` is held.
+    fn entering(sequence: &[&str], mode: &str) -> Chord {
+        Chord {
+            action: ChordAction::EnterMode {
+                enter_mode: mode.to_owned(),
+            },
+            ..chord(sequence, "")
+        }
+    }
+
+    fn bindings(chords: Vec<Chord>) -> Bindings {
+        Bindings {
+            chords,
+            chord_opts: ChordOpts::default(),
+            shell: None,
+            modes: Default::default(),
+            max_hold: None,
+            max_hold_check: false,
+            axis_hysteresis_percent: 5,
+        }
+    }
+
+    #[test]
+    fn match_non_exclusive_passthrough() {
+        let chords = vec![
+            Chord {
+                name: None,
+                sequence: vec!["ctrl", "a"].parse(),
+                steps: Vec::new(),
+                trigger: Trigger::Press,
+                mode: None,
+                action: ChordAction::Shell("one".to_owned()),
+                repeat: None,
+                options: None,
+            },
+            Chord {
+                name: None,
+                sequence: vec!["ctrl", "a", "b"].parse(),
+                steps: Vec::new(),
+                trigger: Trigger::Press,
+                mode: None,
+                action: ChordAction::Shell("two".to_owned()),
+                repeat: None,
+                options: None,
+            },
+            Chord {
+                name: None,
+                sequence: vec!["ctrl", "a", "b", "c"].parse(),
+                steps: Vec::new(),
+                trigger: Trigger::Press,
+                mode: None,
+                action: ChordAction::Shell("three".to_owned()),
+                repeat: None,
+                options: None,
+            },
+            Chord {
+                name: None,
+                sequence: vec!["ctrl", "a", "b", "z"].parse(),
+                steps: Vec::new(),
+                trigger: Trigger::Press,
+                mode: None,
+                action: ChordAction::Shell("not matching".to_owned()),
+                repeat: None,
+                options: None,
+            },
+        ];
+
+        let state = KeyboardState::from_iter([Key::LeftCtrl, Key::A, Key::B, Key::C, Key::D]);
+        let chord_opts = ChordOpts {
+            passthrough: true,
+            others: Others::Allow,
+            ..Default::default()
+        };
+        let actions = match_chords(&state, &chords, &chord_opts, None)
+            .map(|(_, chord)| &chord.action)
+            .collect::<Vec<_>>();
+
+        cool_asserts::assert_matches!(actions[0], ChordAction::Shell(b) if b == "one");
+        cool_asserts::assert_matches!(actions[1], ChordAction::Shell(b) if b == "two");
+        cool_asserts::assert_matches!(actions[2], ChordAction::Shell(b) if b == "three");
+        assert_eq!(actions.len(), 3);
+    }
+
+    #[test]
+    fn match_non_exclusive_non_passthrough_part() {
+        let chords = vec![
+            Chord {
+                name: None,
+                sequence: vec!["ctrl", "a"].parse(),
+                steps: Vec::new(),
+                trigger: Trigger::Press,
+                mode: None,
+                action: ChordAction::Shell("one".to_owned()),
+                repeat: None,
+                options: None,
+            },
+            Chord {
+                name: None,
+                sequence: vec!["ctrl", "a", "b"].parse(),
+                steps: Vec::new(),
+                trigger: Trigger::Press,
+                mode: None,
+                action: ChordAction::Shell("two".to_owned()),
+                repeat: None,
+                options: Some(ChordOptsChild {
+                    passthrough: Some(false),
+                    others: None,
+                    ..Default::default()
+                }),
+            },
+            Chord {
+                name: None,
+                sequence: vec!["ctrl", "a", "b", "c"].parse(),
+                steps: Vec::new(),
+                trigger: Trigger::Press,
+                mode: None,
+                action: ChordAction::Shell("three".to_owned()),
+                repeat: None,
+                options: None,
+            },
+            Chord {
+                name: None,
+                sequence: vec!["ctrl", "a", "b", "z"].parse(),
+                steps: Vec::new(),
+                trigger: Trigger::Press,
+                mode: None,
+                action: ChordAction::Shell("not matching".to_owned()),
+                repeat: None,
+                options: None,
+            },
+        ];
+
+        let state = KeyboardState::from_iter([Key::LeftCtrl, Key::A, Key::B, Key::C, Key::D]);
+        let chord_opts = ChordOpts {
+            passthrough: true,
+            others: Others::Allow,
+            ..Default::default()
+        };
+        let actions = match_chords(&state, &chords, &chord_opts, None)
+            .map(|(_, chord)| &chord.action)
+            .collect::<Vec<_>>();
+
+        cool_asserts::assert_matches!(actions[0], ChordAction::Shell(b) if b == "one");
+        cool_asserts::assert_matches!(actions[1], ChordAction::Shell(b) if b == "two");
+        assert_eq!(actions.len(), 2);
+    }
+
+    #[test]
+    fn match_exclusive() {
+        let chords = vec![
+            Chord {
+                name: None,
+                sequence: vec!["ctrl", "a"].parse(),
+                steps: Vec::new(),
+                trigger: Trigger::Press,
+                mode: None,
+                action: ChordAction::Shell("one".to_owned()),
+                repeat: None,
+                options: None,
+            },
+            Chord {
+                name: None,
+                sequence: vec!["ctrl", "a", "b"].parse(),
+                steps: Vec::new(),
+                trigger: Trigger::Press,
+                mode: None,
+                action: ChordAction::Shell("two".to_owned()),
+                repeat: None,
+                options: Some(ChordOptsChild {
+                    passthrough: None,
+                    others: Some(Others::Forbid),
+                    ..Default::default()
+                }),
+            },
+            Chord {
+                name: None,
+                sequence: vec!["ctrl", "a", "b", "c"].parse(),
+                steps: Vec::new(),
+                trigger: Trigger::Press,
+                mode: None,
+                action: ChordAction::Shell("three".to_owned()),
+                repeat: None,
+                options: None,
+            },
+            Chord {
+                name: None,
+                sequence: vec!["ctrl", "a", "b", "z"].parse(),
+                steps: Vec::new(),
+                trigger: Trigger::Press,
+                mode: None,
+                action: ChordAction::Shell("not matching".to_owned()),
+                repeat: None,
+                options: None,
+            },
+        ];
+
+        let state = KeyboardState::from_iter([Key::LeftCtrl, Key::A, Key::B, Key::C, Key::D]);
+        let chord_opts = ChordOpts {
+            passthrough: true,
+            others: Others::Allow,
+            ..Default::default()
+        };
+        let actions = match_chords(&state, &chords, &chord_opts, None)
+            .map(|(_, chord)| &chord.action)
+            .collect::<Vec<_>>();
+
+        cool_asserts::assert_matches!(actions[0], ChordAction::Shell(b) if b == "one");
+        cool_asserts::assert_matches!(actions[1], ChordAction::Shell(b) if b == "three");
+        assert_eq!(actions.len(), 2);
+    }
+
+    #[test]
+    fn match_negated_keys_and_modifiers_only() {
+        let with_others = |sequence: &[&str], action: &str, others| Chord {
+            options: Some(ChordOptsChild {
+                others,
+                ..Default::default()
+            }),
+            ..chord(sequence, action)
+        };
+        let chords = [
+            with_others(&["ctrl", "!shift", "k"], "plain", None),
+            with_others(&["ctrl", "shift", "k"], "shifted", None),
+            with_others(&["k"], "modified", Some(Others::ModifiersOnly)),
+        ];
+        let chord_opts = ChordOpts::default();
+        let fired = |keys: &[Key]| {
+            let state = KeyboardState::from_iter(keys.iter().copied());
+            match_chords(&state, &chords, &chord_opts, None)
+                .map(|(_, chord)| match &chord.action {
+                    ChordAction::Shell(action) => action.clone(),
+                    _ => unreachable!(),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(fired(&[Key::LeftCtrl, Key::K]), ["plain", "modified"]);
+        assert_eq!(
+            fired(&[Key::LeftCtrl, Key::RightShift, Key::K]),
+            ["shifted", "modified"]
+        );
+        assert!(fired(&[Key::J, Key::K]).is_empty());
+    }
+
+    #[test]
+    fn match_ordered() {
+        let chords = |sequence: &[&str]| {
+            [Chord {
+                options: Some(ChordOptsChild {
+                    ordered: Some(true),
+                    ..Default::default()
+                }),
+                ..chord(sequence, "ordered")
+            }]
+        };
+        let chord_opts = ChordOpts::default();
+        let matches = |sequence: &[&str], state: &KeyboardState| {
+            match_chords(state, &chords(sequence), &chord_opts, None).count() == 1
+        };
+        let pressed = |keys: &[Key]| KeyboardState::from_iter(keys.iter().copied());
+
+        assert!(matches(&["ctrl", "a"], &pressed(&[Key::LeftCtrl, Key::A])));
+        assert!(!matches(&["ctrl", "a"], &pressed(&[Key::A, Key::LeftCtrl])));
+        // either ctrl will do, as long as one went down first
+        let rolled = pressed(&[Key::RightCtrl, Key::A, Key::LeftCtrl]);
+        assert!(matches(&["ctrl", "a"], &rolled));
+        // or after, when it is listed last
+        let rolled = pressed(&[Key::LeftCtrl, Key::A, Key::RightCtrl]);
+        assert!(matches(&["a", "ctrl"], &rolled));
+        assert!(!matches(&["a", "ctrl"], &pressed(&[Key::LeftCtrl, Key::A])));
+
+        // a key on a named input counts from when it went down there
+        let mut state = KeyboardState::default();
+        let now = Instant::now();
+        state.press(Key::A, Some("pedal".into()), now);
+        state.press(Key::LeftCtrl, Some("kbd".into()), now);
+        assert!(!matches(&["ctrl", "kbd:a"], &state));
+        assert!(matches(&["pedal:a", "ctrl"], &state));
+        state.press(Key::A, Some("kbd".into()), now);
+        assert!(matches(&["ctrl", "kbd:a"], &state));
+        assert!(!matches(&["ctrl", "pedal:a"], &state));
+    }
+
+    #[test]
+    fn consume_only_the_keys_completing_a_chord() {
+        let chords = vec![Chord {
+            options: Some(ChordOptsChild {
+                consume: Some(true),
+                ..Default::default()
+            }),
+            ..chord(&["ctrl", "k"], "consumed")
+        }];
+        let (sender, verdicts) = crossbeam_channel::unbounded();
+        let mut handler = Handler::new(bindings(chords), Some(sender), None);
+        let start = Instant::now();
+        let press = |handler: &mut Handler, key| {
+            assert!(handler.handle(Event::Pressed(key, None, start)));
+            verdicts.try_recv().expect("Verdict")
+        };
+
+        assert_eq!(press(&mut handler, Key::LeftCtrl), Verdict::Forward);
+        assert_eq!(press(&mut handler, Key::K), Verdict::Consume);
+        // the chord still matches, but shift is not one of its keys
+        assert_eq!(press(&mut handler, Key::LeftShift), Verdict::Forward);
+        handler.handle(Event::Released(Key::K, None, start));
+        handler.handle(Event::Released(Key::LeftShift, None, start));
+        assert!(verdicts.is_empty(), "Releases need no verdict");
+        assert_eq!(press(&mut handler, Key::J), Verdict::Forward);
+    }
+
+    #[test]
+    fn hold_back_keys_until_the_window_decides() {
+        let chords = vec![Chord {
+            options: Some(ChordOptsChild {
+                window_ms: Some(50),
+                consume: Some(true),
+                ..Default::default()
+            }),
+            ..chord(&["a", "s"], "a and s together")
+        }];
+        let (sender, verdicts) = crossbeam_channel::unbounded();
+        let mut handler = Handler::new(bindings(chords), Some(sender), None);
+        let start = Instant::now();
+        let ms = Duration::from_millis;
+        let press = |handler: &mut Handler, key, at| {
+            assert!(handler.handle(Event::Pressed(key, None, at)));
+            verdicts.try_recv().expect("Verdict")
+        };
+
+        assert_eq!(
+            press(&mut handler, Key::A, start),
+            Verdict::Hold(start + ms(50))
+        );
+        assert_eq!(
+            press(&mut handler, Key::S, start + ms(20)),
+            Verdict::Consume
+        );
+        assert_eq!(handler.fired, [0]);
+        handler.handle(Event::Released(Key::A, None, start + ms(100)));
+        handler.handle(Event::Released(Key::S, None, start + ms(100)));
+
+        // a key not part of the chord passes on those held back
+        let start = start + ms(1000);
+        assert_eq!(
+            press(&mut handler, Key::S, start),
+            Verdict::Hold(start + ms(50))
+        );
+        assert_eq!(
+            press(&mut handler, Key::D, start + ms(10)),
+            Verdict::Forward
+        );
+        assert!(handler.held_back.is_empty());
+        // the chord still fires in the window, though s already reached the desktop
+        assert_eq!(
+            press(&mut handler, Key::A, start + ms(20)),
+            Verdict::Consume
+        );
+        assert_eq!(handler.fired, [0, 0]);
+        handler.handle(Event::Released(Key::A, None, start + ms(100)));
+        handler.handle(Event::Released(Key::D, None, start + ms(100)));
+        handler.handle(Event::Released(Key::S, None, start + ms(100)));
+
+        // too late, the backend passed on a by the time s goes down
+        let start = start + ms(1000);
+        assert_eq!(
+            press(&mut handler, Key::A, start),
+            Verdict::Hold(start + ms(50))
+        );
+        assert_eq!(
+            press(&mut handler, Key::S, start + ms(60)),
+            Verdict::Forward
+        );
+        assert!(handler.held_back.is_empty());
+        handler.handle(Event::Released(Key::A, None, start + ms(100)));
+        handler.handle(Event::Released(Key::S, None, start + ms(100)));
+
+        // releasing a key held back passes it on
+        let start = start + ms(1000);
+        assert_eq!(
+            press(&mut handler, Key::A, start),
+            Verdict::Hold(start + ms(50))
+        );
+        handler.handle(Event::Released(Key::A, None, start + ms(10)));
+        assert!(handler.held_back.is_empty());
+        assert_eq!(handler.fired, [0, 0]);
+    }
+
+    #[test]
+    fn ordered_chords_fire_only_in_order() {
+        let chords = vec![Chord {
+            options: Some(ChordOptsChild {
+                ordered: Some(true),
+                ..Default::default()
+            }),
+            ..chord(&["alt", "d"], "alt then d")
+        }];
+        let mut handler = Handler::new(bindings(chords), None, None);
+        let start = Instant::now();
+
+        // rolling over d then alt while typing
+        handler.handle(Event::Pressed(Key::D, None, start));
+        handler.handle(Event::Pressed(Key::LeftAlt, None, start));
+        handler.handle(Event::Released(Key::D, None, start));
+        assert!(handler.fired.is_empty());
+
+        // alt is still held from before, so d going down now comes after it
+        handler.handle(Event::Pressed(Key::D, None, start));
+        assert_eq!(handler.fired, [0]);
+    }
+
+    #[test]
+    fn sync_keeps_the_order_of_held_keys() {
+        let chords = vec![Chord {
+            options: Some(ChordOptsChild {
+                ordered: Some(true),
+                ..Default::default()
+            }),
+            ..chord(&["ctrl", "alt", "d"], "ctrl then alt then d")
+        }];
+        let mut handler = Handler::new(bindings(chords), None, None);
+        let start = Instant::now();
+
+        handler.handle(Event::Pressed(Key::LeftAlt, None, start));
+        handler.handle(Event::Pressed(
+            Key::LeftCtrl,
+            None,
+            start + Duration::from_millis(10),
+        ));
+        handler.handle(Event::Pressed(Key::Esc, None, start));
+        handler.handle(Event::Sync(
+            maplit::hashset! { (Key::LeftAlt, None), (Key::LeftCtrl, None) },
+        ));
+        assert_eq!(
+            handler.keyboard_state.pressed_at(&Key::LeftAlt),
+            Some(start)
+        );
+        assert!(!handler.keyboard_state.contains(&Key::Esc, None));
+
+        // alt still went down before ctrl
+        handler.handle(Event::Pressed(Key::D, None, start));
+        assert!(handler.fired.is_empty());
+    }
+
+    #[test]
+    fn modes_switch_and_time_out() {
+        let chords = vec![
+            entering(&["meta", "m"], "media"),
+            Chord {
+                mode: Some("media".to_owned()),
+                action: ChordAction::ExitMode { exit_mode: true },
+                ..chord(&["esc"], "")
+            },
+        ];
+        let modes = maplit::hashmap! {
+            "media".to_owned() => Mode { timeout_ms: Some(1000), on_enter: None, on_exit: None },
+        };
+        let bindings = Bindings {
+            modes,
+            ..bindings(chords)
+        };
+        let mut handler = Handler::new(bindings, None, None);
+        let start = Instant::now();
+
+        // esc does nothing outside of the mode
+        handler.handle(Event::Pressed(Key::Esc, None, start));
+        handler.handle(Event::Released(Key::Esc, None, start));
+        assert!(handler.fired.is_empty());
+        assert_eq!(handler.mode, None);
+
+        handler.handle(Event::Pressed(Key::LeftMeta, None, start));
+        handler.handle(Event::Pressed(Key::M, None, start));
+        handler.handle(Event::Released(Key::M, None, start));
+        handler.handle(Event::Released(Key::LeftMeta, None, start));
+        assert_eq!(handler.fired, [0]);
+        assert_eq!(handler.mode.as_deref(), Some("media"));
+
+        handler.handle(Event::Pressed(Key::Esc, None, start));
+        handler.handle(Event::Released(Key::Esc, None, start));
+        assert_eq!(handler.fired, [0, 1]);
+        assert_eq!(handler.mode, None);
+
+        handler.handle(Event::Pressed(Key::LeftMeta, None, start));
+        handler.handle(Event::Pressed(Key::M, None, start));
+        let deadline = handler.deadline().expect("Mode timeout");
+        assert_eq!(deadline, start + Duration::from_millis(1000));
+        handler.tick(deadline);
+        assert_eq!(handler.mode, None);
+    }
+
+    #[test]
+    fn sync_replaces_held_keys_without_firing() {
+        let chords = vec![chord(&["meta", "m"], "media")];
+        let mut handler = Handler::new(bindings(chords), None, None);
+        let start = Instant::now();
+
+        handler.handle(Event::Pressed(Key::Esc, None, start));
+        handler.handle(Event::Sync(
+            maplit::hashset! { (Key::LeftMeta, None), (Key::M, None) },
+        ));
+        assert_eq!(
+            handler
+                .keyboard_state
+                .sources()
+                .map(|(key, _)| *key)
+                .collect::<HashSet<_>>(),
+            maplit::hashset! { Key::LeftMeta, Key::M }
+        );
+        assert!(handler.fired.is_empty());
+
+        handler.handle(Event::Released(Key::M, None, start));
+        handler.handle(Event::Pressed(Key::M, None, start));
+        assert_eq!(handler.fired, [0]);
+    }
+
+    /// A handler firing on meta+m, with or without `retrigger`.
+    fn meta_m_handler(retrigger: bool) -> Handler {
+        let chords = vec![Chord {
+            options: Some(ChordOptsChild {
+                retrigger: Some(retrigger),
+                ..Default::default()
+            }),
+            ..chord(&["meta", "m"], "meta+m")
+        }];
+        Handler::new(bindings(chords), None, None)
+    }
+
+    #[test]
+    fn chords_fire_once_until_their_keys_go_up() {
+        let mut handler = meta_m_handler(false);
+        let start = Instant::now();
+
+        handler.handle(Event::Pressed(Key::LeftMeta, None, start));
+        handler.handle(Event::Pressed(Key::M, None, start));
+        assert_eq!(handler.fired, [0]);
+
+        // other keys coming and going do not fire it again
+        handler.handle(Event::Pressed(Key::LeftShift, None, start));
+        handler.handle(Event::Released(Key::LeftShift, None, start));
+        assert_eq!(handler.fired, [0]);
+
+        handler.handle(Event::Released(Key::M, None, start));
+        handler.handle(Event::Pressed(Key::M, None, start));
+        assert_eq!(handler.fired, [0, 0]);
+
+        // pressing the other meta adds no new activation either
+        handler.handle(Event::Pressed(Key::RightMeta, None, start));
+        handler.handle(Event::Released(Key::LeftMeta, None, start));
+        assert_eq!(handler.fired, [0, 0]);
+    }
+
+    #[test]
+    fn retrigger_fires_on_every_event() {
+        let mut handler = meta_m_handler(true);
+        let start = Instant::now();
+
+        handler.handle(Event::Pressed(Key::LeftMeta, None, start));
+        handler.handle(Event::Pressed(Key::M, None, start));
+        assert_eq!(handler.fired, [0]);
+        handler.handle(Event::Pressed(Key::LeftShift, None, start));
+        assert_eq!(handler.fired, [0, 0]);
+        handler.handle(Event::Released(Key::LeftShift, None, start));
+        assert_eq!(handler.fired, [0, 0, 0]);
+        handler.handle(Event::Released(Key::M, None, start));
+        assert_eq!(handler.fired, [0, 0, 0]);
+    }
+
+    #[test]
+    fn window_needs_keys_pressed_together_and_fires_once_all_are_up() {
+        let chords = vec![Chord {
+            trigger: Trigger::Release,
+            options: Some(ChordOptsChild {
+                window_ms: Some(50),
+                ..Default::default()
+            }),
+            ..chord(&["a", "s"], "steno")
+        }];
+        let mut handler = Handler::new(bindings(chords), None, None);
+        let start = Instant::now();
+        let ms = Duration::from_millis;
+
+        // typed one after the other
+        handler.handle(Event::Pressed(Key::A, None, start));
+        handler.handle(Event::Pressed(Key::S, None, start + ms(200)));
+        handler.handle(Event::Released(Key::A, None, start + ms(300)));
+        handler.handle(Event::Released(Key::S, None, start + ms(300)));
+        assert!(handler.fired.is_empty());
+
+        // just outside the window
+        let start = start + ms(1000);
+        handler.handle(Event::Pressed(Key::A, None, start));
+        handler.handle(Event::Pressed(Key::S, None, start + ms(51)));
+        handler.handle(Event::Released(Key::A, None, start + ms(100)));
+        handler.handle(Event::Released(Key::S, None, start + ms(100)));
+        assert!(handler.fired.is_empty());
+
+        // just inside it
+        let start = start + ms(1000);
+        handler.handle(Event::Pressed(Key::S, None, start));
+        handler.handle(Event::Pressed(Key::A, None, start + ms(50)));
+        handler.handle(Event::Released(Key::A, None, start + ms(100)));
+        assert!(handler.fired.is_empty());
+        handler.handle(Event::Released(Key::S, None, start + ms(150)));
+        assert_eq!(handler.fired, [0]);
+    }
+
+    #[test]
+    fn stuck_keys_expire() {
+        let bindings = Bindings {
+            max_hold: Some(Duration::from_secs(60)),
+            ..bindings(Vec::new())
+        };
+        let mut handler = Handler::new(bindings, None, None);
+        let start = Instant::now();
+
+        handler.handle(Event::Pressed(Key::LeftCtrl, None, start));
+        handler.handle(Event::Pressed(
+            Key::A,
+            None,
+            start + Duration::from_secs(30),
+        ));
+        let deadline = handler.deadline().expect("Hold deadline");
+        assert_eq!(deadline, start + Duration::from_secs(60));
+
+        handler.tick(deadline + Duration::from_millis(1));
+        assert!(!handler.keyboard_state.contains(&Key::LeftCtrl, None));
+        assert!(handler.keyboard_state.contains(&Key::A, None));
+    }
+
+    #[test]
+    fn switches_outlive_max_hold() {
+        let bindings = Bindings {
+            max_hold: Some(Duration::from_secs(60)),
+            ..bindings(vec![chord(&["sw_lid", "f1"], "docked")])
+        };
+        let mut handler = Handler::new(bindings, None, None);
+        let start = Instant::now();
+
+        handler.handle(Event::Pressed(Key::SwLid, None, start));
+        assert_eq!(handler.deadline(), None);
+        let later = start + Duration::from_secs(3600);
+        handler.tick(later);
+        assert!(handler.keyboard_state.contains(&Key::SwLid, None));
+
+        handler.handle(Event::Pressed(Key::F1, None, later));
+        assert_eq!(handler.fired, [0]);
+    }
+
+    #[test]
+    fn match_keys_of_named_inputs() {
+        let chords = [
+            chord(&["pedal:f13", "a"], "pedal"),
+            chord(&["kb:f13", "kb:a"], "keyboard only"),
+        ];
+        let chord_opts = ChordOpts {
+            others: Others::Forbid,
+            ..Default::default()
+        };
+        let now = Instant::now();
+        let mut state = KeyboardState::default();
+        state.press(Key::F13, Some("pedal".into()), now);
+        state.press(Key::A, Some("kb".into()), now);
+        let actions = match_chords(&state, &chords, &chord_opts, None)
+            .map(|(_, chord)| &chord.action)
+            .collect::<Vec<_>>();
+        cool_asserts::assert_matches!(actions[0], ChordAction::Shell(b) if b == "pedal");
+        assert_eq!(actions.len(), 1);
+
+        // f13 is now held on the keyboard too, which the exclusive pedal chord does not allow
+        state.press(Key::F13, Some("kb".into()), now);
+        assert_eq!(match_chords(&state, &chords, &chord_opts, None).count(), 0);
+        state.release(&Key::F13, &Some("pedal".into()));
+        let actions = match_chords(&state, &chords, &chord_opts, None)
+            .map(|(_, chord)| &chord.action)
+            .collect::<Vec<_>>();
+        cool_asserts::assert_matches!(actions[0], ChordAction::Shell(b) if b == "keyboard only");
+        assert_eq!(actions.len(), 1);
+    }
+
+    #[test]
+    fn axis_thresholds_press_keys_with_hysteresis() {
+        let chords = vec![chord(&["pad_tl", "left_trigger>50%"], "pad")];
+        let mut handler = Handler::new(bindings(chords), None, None);
+        let trigger = Key::Threshold(Threshold {
+            axis: Axis::LeftTrigger,
+            above: true,
+            percent: 50,
+        });
+        assert_eq!(key::get_key_for_name("left_trigger>50%"), Some(trigger));
+        let start = Instant::now();
+
+        handler.handle(Event::Axis(Axis::LeftTrigger, None, 40, start));
+        assert!(!handler.keyboard_state.contains(&trigger, None));
+        handler.handle(Event::Pressed(Key::PadTl, None, start));
+        assert!(handler.fired.is_empty());
+        handler.handle(Event::Axis(Axis::LeftTrigger, None, 60, start));
+        assert!(handler.keyboard_state.contains(&trigger, None));
+        assert_eq!(handler.fired, [0]);
+
+        // jitter around the threshold does not release it
+        handler.handle(Event::Axis(Axis::LeftTrigger, None, 47, start));
+        assert!(handler.keyboard_state.contains(&trigger, None));
+        handler.handle(Event::Axis(Axis::LeftTrigger, None, 44, start));
+        assert!(!handler.keyboard_state.contains(&trigger, None));
+        handler.handle(Event::Axis(Axis::LeftTrigger, None, 60, start));
+        assert_eq!(handler.fired, [0, 0]);
+
+        handler.handle(Event::Axis(Axis::HatY, None, -100, start));
+        assert!(handler.keyboard_state.contains(&Key::HatUp, None));
+        handler.handle(Event::Axis(Axis::HatY, None, 100, start));
+        assert!(!handler.keyboard_state.contains(&Key::HatUp, None));
+        assert!(handler.keyboard_state.contains(&Key::HatDown, None));
+
+        // the d-pad button of the same direction is a key of its own
+        handler.handle(Event::Pressed(Key::DpadDown, None, start));
+        handler.handle(Event::Axis(Axis::HatY, None, 0, start));
+        assert!(!handler.keyboard_state.contains(&Key::HatDown, None));
+        assert!(handler.keyboard_state.contains(&Key::DpadDown, None));
+        handler.handle(Event::Released(Key::DpadDown, None, start));
+        assert!(!handler.keyboard_state.contains(&Key::DpadDown, None));
+    }
+}
@@ -0,0 +1,145 @@
+use crate::{backend::Source, key::Key};
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet},
+    time::Instant,
+};
+
+/// The keys held on a backend, along with when each of them went down and on which inputs.
+#[derive(Default, Debug)]
+pub struct KeyboardState {
+    pressed: HashMap<Key, Held>,
+    /// Counts the keys that went down, to tell in which order they did.
+    presses: u64,
+}
+
+#[derive(Debug)]
+struct Held {
+    /// When the key first went down on any input.
+    since: Instant,
+    /// Each input holding the key, with how many keys went down before it did there.
+    sources: HashMap<Source, u64>,
+}
+
+impl KeyboardState {
+    /// Whether the key is held on the named input, or on any input if none is given.
+    pub fn contains(&self, key: &Key, input: Option<&str>) -> bool {
+        match (self.pressed.get(key), input) {
+            (None, _) => false,
+            (Some(_), None) => true,
+            (Some(held), Some(input)) => held
+                .sources
+                .keys()
+                .any(|source| source.as_deref() == Some(input)),
+        }
+    }
+
+    /// When the key went down on the first input still holding it.
+    pub fn pressed_at(&self, key: &Key) -> Option<Instant> {
+        Some(self.pressed.get(key)?.since)
+    }
+
+    /// How many keys went down before the key did on each input holding it, or only on the
+    /// named input if one is given, to compare with others.
+    pub fn press_orders<'a>(
+        &'a self,
+        key: &Key,
+        input: Option<&'a str>,
+    ) -> impl Iterator<Item = u64> + 'a {
+        let sources = self
+            .pressed
+            .get(key)
+            .into_iter()
+            .flat_map(|held| &held.sources);
+        sources
+            .filter(move |(source, _)| input.is_none_or(|input| source.as_deref() == Some(input)))
+            .map(|(_, order)| *order)
+    }
+
+    /// Every key along with each input holding it.
+    pub fn sources(&self) -> impl Iterator<Item = (&Key, Option<&str>)> {
+        self.pressed.iter().flat_map(|(key, held)| {
+            held.sources
+                .keys()
+                .map(move |source| (key, source.as_deref()))
+        })
+    }
+
+    /// Returns false if the key was already held on that source.
+    pub fn press(&mut self, key: Key, source: Source, at: Instant) -> bool {
+        let order = self.presses;
+        let held = self.pressed.entry(key).or_insert_with(|| Held {
+            since: at,
+            sources: HashMap::new(),
+        });
+        self.presses += 1;
+        match held.sources.entry(source) {
+            Entry::Vacant(entry) => {
+                entry.insert(order);
+                true
+            }
+            Entry::Occupied(_) => false,
+        }
+    }
+
+    /// Returns false if the key was not held on that source.
+    pub fn release(&mut self, key: &Key, source: &Source) -> bool {
+        let Some(held) = self.pressed.get_mut(key) else {
+            return false;
+        };
+        let released = held.sources.remove(source).is_some();
+        if held.sources.is_empty() {
+            self.pressed.remove(key);
+        }
+        released
+    }
+
+    pub fn clear(&mut self) {
+        self.pressed.clear();
+    }
+
+    /// Replaces the held keys. Those already held keep when and in which order they went down,
+    /// the others are taken as going down `at`.
+    pub fn sync(&mut self, keys: HashSet<(Key, Source)>, at: Instant) {
+        self.pressed.retain(|key, held| {
+            held.sources
+                .retain(|source, _| keys.contains(&(*key, source.clone())));
+            !held.sources.is_empty()
+        });
+        for (key, source) in keys {
+            self.press(key, source, at);
+        }
+    }
+
+    /// When the key held the longest went down, among those that can get stuck.
+    pub fn oldest(&self) -> Option<Instant> {
+        let stuck = self.pressed.iter().filter(|(key, _)| !key.is_latched());
+        stuck.map(|(_, held)| held.since).min()
+    }
+
+    /// Forgets the keys held since before `since`, returning them. [Latched](Key::is_latched)
+    /// keys are kept, as they are released along with the state they follow.
+    pub fn expire(&mut self, since: Instant) -> Vec<Key> {
+        let expired = self
+            .pressed
+            .iter()
+            .filter(|(key, held)| held.since < since && !key.is_latched())
+            .map(|(key, _)| *key)
+            .collect::<Vec<_>>();
+        for key in &expired {
+            self.pressed.remove(key);
+        }
+        expired
+    }
+}
+
+/// Holds the keys as if they all went down just now, on a backend without named inputs.
+impl FromIterator<Key> for KeyboardState {
+    fn from_iter<T: IntoIterator<Item = Key>>(keys: T) -> Self {
+        let mut state = Self::default();
+        let now = Instant::now();
+        for key in keys {
+            state.press(key, None, now);
+        }
+        state
+    }
+}
@@ -0,0 +1,294 @@
+use crate::{
+    chord::{holds_keys, state::KeyboardState},
+    config::{Chord, ChordOpts, Trigger},
+};
+use std::time::{Duration, Instant};
+
+/// Tracks when each chord started and stopped matching, for the triggers that depend on timing,
+/// and which chords triggered on press already fired.
+pub struct Triggers {
+    chords: Vec<Activation>,
+}
+
+#[derive(Default, Clone)]
+struct Activation {
+    /// When the chord started matching, if it currently matches.
+    since: Option<Instant>,
+    /// Whether a hold already fired during the current match.
+    held: bool,
+    /// When the last tap ended, for double taps.
+    last_tap: Option<Instant>,
+    /// Whether a chord triggered on press fired and none of its keys went up since.
+    latched: bool,
+    /// When a latched chord with `repeat` runs its action next.
+    repeat_at: Option<Instant>,
+}
+
+impl Triggers {
+    pub fn new(chords: &[Chord]) -> Self {
+        Self {
+            chords: vec![Activation::default(); chords.len()],
+        }
+    }
+
+    /// Returns the indices of the chords triggered on press that should fire with the `matched`
+    /// chords. A chord fires once when it starts matching, then stays latched until one of its
+    /// keys is released, unless it asks to `retrigger` on every event.
+    pub fn press(
+        &mut self,
+        matched: &[usize],
+        chords: &[Chord],
+        chord_opts: &ChordOpts,
+        state: &KeyboardState,
+        now: Instant,
+    ) -> Vec<usize> {
+        let mut fired = Vec::new();
+        for (index, (chord, activation)) in chords.iter().zip(&mut self.chords).enumerate() {
+            if chord.trigger != Trigger::Press {
+                continue;
+            }
+            if !holds_keys(state, &chord.sequence) {
+                activation.latched = false;
+                activation.repeat_at = None;
+            }
+            if !matched.contains(&index) {
+                continue;
+            }
+            if !activation.latched || chord_opts.merged(chord.options).retrigger {
+                fired.push(index);
+                if let Some(repeat) = chord.repeat {
+                    activation.repeat_at =
+                        Some(now + Duration::from_millis(repeat.initial_delay_ms));
+                }
+            }
+            activation.latched = true;
+        }
+        fired
+    }
+
+    /// Updates the activations with the chords matching at `now`, returning the indices of the
+    /// chords that fired. Chords triggered on press are not tracked here.
+    pub fn update(
+        &mut self,
+        matched: &[usize],
+        chords: &[Chord],
+        chord_opts: &ChordOpts,
+        now: Instant,
+    ) -> Vec<usize> {
+        let mut fired = Vec::new();
+        for (index, (chord, activation)) in chords.iter().zip(&mut self.chords).enumerate() {
+            if chord.trigger == Trigger::Press {
+                continue;
+            }
+            let opts = chord_opts.merged(chord.options);
+            let tap = Duration::from_millis(opts.tap_ms);
+            match (activation.since, matched.contains(&index)) {
+                (None, true) => {
+                    activation.since = Some(now);
+                    if chord.trigger == Trigger::DoubleTap {
+                        if let Some(last_tap) = activation.last_tap.take() {
+                            if now.saturating_duration_since(last_tap) <= tap {
+                                fired.push(index);
+                            }
+                        }
+                    }
+                }
+                (Some(since), false) => {
+                    let short = now.saturating_duration_since(since) <= tap;
+                    match chord.trigger {
+                        Trigger::Release => fired.push(index),
+                        Trigger::Tap if short => fired.push(index),
+                        Trigger::DoubleTap if short => activation.last_tap = Some(now),
+                        _ => {}
+                    }
+                    activation.since = None;
+                    activation.held = false;
+                }
+                _ => {}
+            }
+        }
+        fired.extend(self.tick(chords, chord_opts, now));
+        fired
+    }
+
+    /// Fires the holds that are due at `now`.
+    pub fn tick(&mut self, chords: &[Chord], chord_opts: &ChordOpts, now: Instant) -> Vec<usize> {
+        let mut fired = Vec::new();
+        for (index, (chord, activation)) in chords.iter().zip(&mut self.chords).enumerate() {
+            if chord.trigger != Trigger::Hold || activation.held {
+                continue;
+            }
+            let Some(since) = activation.since else {
+                continue;
+            };
+            let hold = Duration::from_millis(chord_opts.merged(chord.options).hold_ms);
+            if now.saturating_duration_since(since) >= hold {
+                activation.held = true;
+                fired.push(index);
+            }
+        }
+        fired
+    }
+
+    /// Returns the chords held long enough to repeat at `now`, scheduling their next repeat.
+    pub fn repeats(&mut self, chords: &[Chord], now: Instant) -> Vec<usize> {
+        let mut due = Vec::new();
+        for (index, (chord, activation)) in chords.iter().zip(&mut self.chords).enumerate() {
+            let (Some(repeat), Some(at)) = (chord.repeat, activation.repeat_at) else {
+                continue;
+            };
+            if at <= now {
+                // from now rather than `at`, so that a stalled handler does not burst
+                activation.repeat_at = Some(now + Duration::from_millis(repeat.interval_ms));
+                due.push(index);
+            }
+        }
+        due
+    }
+
+    /// The next time [`Triggers::tick`] or [`Triggers::repeats`] has something to do.
+    pub fn deadline(&self, chords: &[Chord], chord_opts: &ChordOpts) -> Option<Instant> {
+        let holds = chords
+            .iter()
+            .zip(&self.chords)
+            .filter(|(chord, activation)| chord.trigger == Trigger::Hold && !activation.held)
+            .filter_map(|(chord, activation)| {
+                let hold = Duration::from_millis(chord_opts.merged(chord.options).hold_ms);
+                Some(activation.since? + hold)
+            });
+        let repeats = self
+            .chords
+            .iter()
+            .filter_map(|activation| activation.repeat_at);
+        holds.chain(repeats).min()
+    }
+
+    /// Whether the chord matched when last updated.
+    pub fn is_active(&self, index: usize) -> bool {
+        self.chords[index].since.is_some()
+    }
+
+    /// Forgets all activations. Chords triggered on press whose keys are all held stay latched,
+    /// as those keys went down before whatever caused the reset.
+    pub fn reset(&mut self, chords: &[Chord], state: &KeyboardState) {
+        for (chord, activation) in chords.iter().zip(&mut self.chords) {
+            *activation = Activation {
+                latched: chord.trigger == Trigger::Press && holds_keys(state, &chord.sequence),
+                ..Activation::default()
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        chord::{state::KeyboardState, trigger::Triggers},
+        config::{Chord, ChordAction, ChordOpts, ConfiguredKey, Repeat, Trigger},
+        key::Key,
+    };
+    use std::{
+        str::FromStr,
+        time::{Duration, Instant},
+    };
+
+    fn capslock(trigger: Trigger) -> Chord {
+        Chord {
+            name: None,
+            sequence: vec![ConfiguredKey::from_str("capslock").expect("Parsing")],
+            steps: Vec::new(),
+            trigger,
+            mode: None,
+            action: ChordAction::Shell(format!("{trigger:?}")),
+            repeat: None,
+            options: None,
+        }
+    }
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn tap_and_hold() {
+        let chords = [capslock(Trigger::Tap), capslock(Trigger::Hold)];
+        let opts = ChordOpts::default();
+        let mut triggers = Triggers::new(&chords);
+        let start = Instant::now();
+
+        // a short press only taps
+        assert!(triggers.update(&[0, 1], &chords, &opts, start).is_empty());
+        assert_eq!(
+            triggers.update(&[], &chords, &opts, start + ms(50)),
+            vec![0]
+        );
+
+        // a long press only holds, once
+        let start = start + ms(1000);
+        assert!(triggers.update(&[0, 1], &chords, &opts, start).is_empty());
+        let due = triggers.deadline(&chords, &opts).expect("Hold pending");
+        assert_eq!(due, start + ms(opts.hold_ms));
+        assert_eq!(triggers.tick(&chords, &opts, due), vec![1]);
+        assert!(triggers.tick(&chords, &opts, due + ms(100)).is_empty());
+        assert!(triggers
+            .update(&[], &chords, &opts, due + ms(200))
+            .is_empty());
+    }
+
+    #[test]
+    fn double_tap() {
+        let chords = [capslock(Trigger::DoubleTap), capslock(Trigger::Release)];
+        let opts = ChordOpts::default();
+        let mut triggers = Triggers::new(&chords);
+        let start = Instant::now();
+
+        assert!(triggers.update(&[0, 1], &chords, &opts, start).is_empty());
+        assert_eq!(
+            triggers.update(&[], &chords, &opts, start + ms(50)),
+            vec![1]
+        );
+        assert_eq!(
+            triggers.update(&[0, 1], &chords, &opts, start + ms(100)),
+            vec![0]
+        );
+        assert_eq!(
+            triggers.update(&[], &chords, &opts, start + ms(150)),
+            vec![1]
+        );
+
+        // too slow to be a double tap
+        let start = start + ms(1000);
+        triggers.update(&[0, 1], &chords, &opts, start);
+        triggers.update(&[], &chords, &opts, start + ms(50));
+        assert!(triggers
+            .update(&[0, 1], &chords, &opts, start + ms(500))
+            .is_empty());
+    }
+
+    #[test]
+    fn repeat_while_held() {
+        let mut chord = capslock(Trigger::Press);
+        chord.repeat = Some(Repeat {
+            initial_delay_ms: 500,
+            interval_ms: 100,
+            max_running: 1,
+        });
+        let chords = [chord];
+        let opts = ChordOpts::default();
+        let mut triggers = Triggers::new(&chords);
+        let held = KeyboardState::from_iter([Key::CapsLock]);
+        let start = Instant::now();
+
+        assert_eq!(triggers.press(&[0], &chords, &opts, &held, start), vec![0]);
+        assert_eq!(triggers.deadline(&chords, &opts), Some(start + ms(500)));
+        assert!(triggers.repeats(&chords, start + ms(400)).is_empty());
+        assert_eq!(triggers.repeats(&chords, start + ms(500)), vec![0]);
+        assert_eq!(triggers.deadline(&chords, &opts), Some(start + ms(600)));
+
+        let released = KeyboardState::default();
+        assert!(triggers
+            .press(&[], &chords, &opts, &released, start + ms(550))
+            .is_empty());
+        assert_eq!(triggers.deadline(&chords, &opts), None);
+    }
+}
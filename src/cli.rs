@@ -1,4 +1,4 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use std::path::PathBuf;
 
 #[derive(Parser)]
@@ -6,4 +6,49 @@ pub struct Cli {
     /// Set an alternate config file. Default is OS-dependent, check docs.
     #[arg(short, long)]
     pub config: Option<PathBuf>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Print keys as they are pressed, with their names for the config.
+    Monitor {
+        /// Read this device instead of the backend of an executor.
+        #[arg(short, long)]
+        device: Option<PathBuf>,
+        /// The executor whose backend to read, by position or name. Defaults to the first one.
+        #[arg(short, long, conflicts_with = "device")]
+        executor: Option<String>,
+    },
+    /// List the input devices, with stable paths to use in the config.
+    #[cfg(feature = "backend-evdev")]
+    ListDevices {
+        /// Print JSON instead, for scripts.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Control the running daemon.
+    Ctl {
+        #[command(subcommand)]
+        command: CtlCommand,
+    },
+}
+
+/// Executors are given by their position in the config (starting at 1) or by their name.
+#[derive(Subcommand)]
+pub enum CtlCommand {
+    /// List the executors and their chords.
+    List,
+    /// Stop an executor from firing chords.
+    Pause { executor: String },
+    /// Let a paused executor fire chords again.
+    Resume { executor: String },
+    /// Fire the action of a named chord, as if it matched.
+    Trigger { executor: String, chord: String },
+    /// Reload the config file.
+    Reload,
+    /// Show the keys an executor currently sees held.
+    Held { executor: String },
 }
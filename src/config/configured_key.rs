@@ -1,14 +1,117 @@
-use crate::key::{self, Key};
+use crate::{
+    config::ValidationError,
+    key::{self, Key},
+};
 use serde::Deserialize;
 use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
     fmt::{Display, Formatter},
     str::FromStr,
 };
 
+thread_local! {
+    /// The `[aliases]` of the config being read, see [`with_aliases`].
+    static ALIASES: RefCell<HashMap<String, Vec<Key>>> = RefCell::new(HashMap::new());
+}
+
+/// Reads keys in `read` with the given aliases, as returned by [`resolve_aliases`], taking
+/// precedence over built-in names.
+pub fn with_aliases<T>(aliases: HashMap<String, Vec<Key>>, read: impl FnOnce() -> T) -> T {
+    /// Clears the aliases however reading ends, even by a panic, so that the next config read on
+    /// the thread does not see them.
+    struct Reset;
+
+    impl Drop for Reset {
+        fn drop(&mut self) {
+            ALIASES.take();
+        }
+    }
+
+    ALIASES.set(aliases);
+    let _reset = Reset;
+    read()
+}
+
+/// Resolves the `[aliases]` table into the keys each alias stands for, following aliases that
+/// refer to others.
+pub fn resolve_aliases(
+    aliases: &BTreeMap<String, String>,
+) -> Result<HashMap<String, Vec<Key>>, ValidationError> {
+    let aliases = aliases
+        .iter()
+        .map(|(name, keys)| (name.to_lowercase(), keys.as_str()))
+        .collect::<BTreeMap<_, _>>();
+    let mut resolved = HashMap::new();
+    for name in aliases.keys() {
+        // keys are split on these before aliases are looked up, so such an alias could not be used
+        if name.is_empty() || name.starts_with('!') || name.contains([':', '|']) {
+            return Err(ValidationError::AliasName {
+                alias: name.clone(),
+            });
+        }
+        resolve_alias(name, &aliases, &mut resolved, &mut Vec::new())?;
+    }
+    Ok(resolved)
+}
+
+/// Resolves an alias along with the aliases it refers to, `path` being those it was reached
+/// through.
+fn resolve_alias(
+    name: &str,
+    aliases: &BTreeMap<String, &str>,
+    resolved: &mut HashMap<String, Vec<Key>>,
+    path: &mut Vec<String>,
+) -> Result<Vec<Key>, ValidationError> {
+    if let Some(keys) = resolved.get(name) {
+        return Ok(keys.clone());
+    }
+    if let Some(start) = path.iter().position(|alias| alias == name) {
+        let mut cycle = path[start..].to_vec();
+        cycle.push(name.to_string());
+        return Err(ValidationError::AliasCycle {
+            cycle: cycle.join(" -> "),
+        });
+    }
+    path.push(name.to_string());
+    let mut keys = Vec::new();
+    for part in aliases[name].split('|').map(str::to_lowercase) {
+        let part_keys = if aliases.contains_key(&part) {
+            resolve_alias(&part, aliases, resolved, path)?
+        } else {
+            built_in(&part).ok_or_else(|| ValidationError::UnknownAliasKey {
+                alias: name.to_string(),
+                key: part,
+            })?
+        };
+        for key in part_keys {
+            if !keys.contains(&key) {
+                keys.push(key);
+            }
+        }
+    }
+    path.pop();
+    resolved.insert(name.to_string(), keys.clone());
+    Ok(keys)
+}
+
+/// The keys a built-in group or key name stands for.
+fn built_in(name: &str) -> Option<Vec<Key>> {
+    match key::key_override(name) {
+        Some(group) => Some(group.to_vec()),
+        None => Some(vec![key::get_key_for_name(name)?]),
+    }
+}
+
 #[derive(Deserialize, Debug)]
 #[serde(try_from = "String")]
 pub struct ConfiguredKey {
     accepted: Vec<Key>,
+    /// The backend input the key has to be held on, written as `input:key`. Any input will do
+    /// when not given.
+    input: Option<String>,
+    /// Whether the key must not be held instead, written as `!key`.
+    negated: bool,
 }
 
 impl ConfiguredKey {
@@ -16,8 +119,36 @@ impl ConfiguredKey {
         self.accepted.iter()
     }
 
-    pub fn matches(&self, key: &Key) -> bool {
-        self.accepted.contains(key)
+    pub fn input(&self) -> Option<&str> {
+        self.input.as_deref()
+    }
+
+    pub fn negated(&self) -> bool {
+        self.negated
+    }
+
+    /// Whether `key` held on the input named `source` is this key. Never true for negated keys,
+    /// which are not part of a chord but keep it from matching.
+    pub fn matches(&self, key: &Key, source: Option<&str>) -> bool {
+        !self.negated
+            && self.accepted.contains(key)
+            && self
+                .input
+                .as_deref()
+                .is_none_or(|input| source == Some(input))
+    }
+}
+
+impl Display for ConfiguredKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.negated {
+            write!(f, "!")?;
+        }
+        if let Some(input) = &self.input {
+            write!(f, "{input}:")?;
+        }
+        let names = self.accepted.iter().map(Key::name);
+        write!(f, "{}", names.collect::<Vec<_>>().join("|"))
     }
 }
 
@@ -25,10 +156,21 @@ impl FromStr for ConfiguredKey {
     type Err = UnknownKey;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (negated, s) = match s.strip_prefix('!') {
+            Some(s) => (true, s),
+            None => (false, s),
+        };
+        let (input, keys) = match s.split_once(':') {
+            Some(_) if s.starts_with(key::RAW_PREFIX) => (None, s),
+            Some((input, keys)) => (Some(input.to_string()), keys),
+            None => (None, s),
+        };
         let mut accepted = Vec::new();
-        for key in s.split('|').map(|a| a.to_lowercase()) {
-            if let Some(overrides) = key::key_override(&key) {
-                accepted.extend(overrides.into_iter());
+        for key in keys.split('|').map(|a| a.to_lowercase()) {
+            if let Some(alias) = ALIASES.with_borrow(|aliases| aliases.get(&key).cloned()) {
+                accepted.extend(alias);
+            } else if let Some(overrides) = key::key_override(&key) {
+                accepted.extend(overrides);
             } else if let Some(k) = key::get_key_for_name(&key) {
                 if accepted.contains(&k) {
                     log::warn!("Duplicate key: {k} in {s}");
@@ -38,7 +180,11 @@ impl FromStr for ConfiguredKey {
                 return Err(UnknownKey { key });
             }
         }
-        Ok(Self { accepted })
+        Ok(Self {
+            accepted,
+            input,
+            negated,
+        })
     }
 }
 
@@ -62,3 +208,106 @@ impl Display for UnknownKey {
 }
 
 impl std::error::Error for UnknownKey {}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        config::{configured_key, ConfiguredKey, ValidationError},
+        key::Key,
+    };
+    use maplit::btreemap;
+    use std::str::FromStr;
+
+    #[test]
+    fn aliases_resolve_before_built_in_names() {
+        let aliases = btreemap! {
+            "hyper".to_string() => "leftmeta|rightctrl".to_string(),
+            "Mods".to_string() => "hyper|shift|leftmeta".to_string(),
+            "a".to_string() => "b".to_string(),
+        };
+        let aliases = configured_key::resolve_aliases(&aliases).expect("Resolving");
+        let (mods, a) = configured_key::with_aliases(aliases, || {
+            let mods = ConfiguredKey::from_str("kb:mods").expect("Parsing");
+            (mods, ConfiguredKey::from_str("a").expect("Parsing"))
+        });
+        assert_eq!(
+            mods.matching().copied().collect::<Vec<_>>(),
+            vec![
+                Key::LeftMeta,
+                Key::RightCtrl,
+                Key::LeftShift,
+                Key::RightShift
+            ]
+        );
+        assert_eq!(mods.input(), Some("kb"));
+        assert_eq!(a.matching().copied().collect::<Vec<_>>(), vec![Key::B]);
+        // out of the config, names mean what they always do
+        assert!(ConfiguredKey::from_str("hyper").is_err());
+        let digit = ConfiguredKey::from_str("digit").expect("Parsing");
+        assert_eq!(digit.matching().count(), 10);
+    }
+
+    #[test]
+    fn alias_cycles_and_unknown_keys_are_errors() {
+        let aliases = btreemap! {
+            "left".to_string() => "right|a".to_string(),
+            "right".to_string() => "up|left".to_string(),
+        };
+        cool_asserts::assert_matches!(
+            configured_key::resolve_aliases(&aliases),
+            Err(ValidationError::AliasCycle { cycle }) if cycle == "left -> right -> left"
+        );
+        let aliases = btreemap! { "hyper".to_string() => "leftmeta|hyperkey".to_string() };
+        cool_asserts::assert_matches!(
+            configured_key::resolve_aliases(&aliases),
+            Err(ValidationError::UnknownAliasKey { alias, key }) if alias == "hyper" && key == "hyperkey"
+        );
+    }
+
+    #[test]
+    fn alias_names_must_be_usable() {
+        for name in ["", "!hyper", "kb:hyper", "hyper|super"] {
+            let aliases = btreemap! { name.to_string() => "leftmeta".to_string() };
+            cool_asserts::assert_matches!(
+                configured_key::resolve_aliases(&aliases),
+                Err(ValidationError::AliasName { alias }) if alias == name
+            );
+        }
+    }
+
+    #[test]
+    fn aliases_are_cleared_after_a_panic() {
+        let aliases = btreemap! { "hyper".to_string() => "leftmeta".to_string() };
+        let aliases = configured_key::resolve_aliases(&aliases).expect("Resolving");
+        let panicked = std::panic::catch_unwind(|| {
+            configured_key::with_aliases(aliases, || panic!("Reading failed"))
+        });
+        assert!(panicked.is_err());
+        assert!(ConfiguredKey::from_str("hyper").is_err());
+    }
+
+    #[test]
+    fn raw_codes_round_trip() {
+        let key = ConfiguredKey::from_str("code:456|0x1c9").expect("Parsing");
+        assert_eq!(
+            key.matching().copied().collect::<Vec<_>>(),
+            vec![Key::Raw(456), Key::Raw(457)]
+        );
+        assert_eq!(key.input(), None);
+        assert_eq!(key.to_string(), "code:456|code:457");
+
+        let key = ConfiguredKey::from_str("pedal:code:456").expect("Parsing");
+        assert_eq!(key.input(), Some("pedal"));
+        assert!(key.matches(&Key::Raw(456), Some("pedal")));
+        assert_eq!(key.to_string(), "pedal:code:456");
+    }
+
+    #[test]
+    fn negated_keys_match_nothing() {
+        let key = ConfiguredKey::from_str("!kb:shift").expect("Parsing");
+        assert!(key.negated());
+        assert_eq!(key.input(), Some("kb"));
+        assert!(!key.matches(&Key::LeftShift, Some("kb")));
+        assert_eq!(key.to_string(), "!kb:leftshift|rightshift");
+    }
+}
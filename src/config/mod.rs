@@ -3,9 +3,8 @@ mod structs;
 
 use crate::{APPLICATION, ORGANIZATION, QUALIFIER};
 use anyhow::{anyhow, Context};
-#[cfg(test)]
-pub use configured_key::*;
-use std::{fs, fs::File, io, path::PathBuf};
+pub use configured_key::ConfiguredKey;
+use std::{collections::BTreeMap, fs, fs::File, io, path::PathBuf};
 pub use structs::*;
 
 fn default_config() -> PathBuf {
@@ -14,11 +13,16 @@ fn default_config() -> PathBuf {
     dirs.config_dir().join(format!("{APPLICATION}.toml"))
 }
 
-pub fn load_config(config_path: Option<PathBuf>) -> anyhow::Result<Config> {
-    let (config_path, defaulted) = match config_path {
+/// Resolves the config path given on the command line, returning whether it was defaulted.
+pub fn config_path(config_path: Option<PathBuf>) -> (PathBuf, bool) {
+    match config_path {
         Some(conf) => (conf, false),
         None => (default_config(), true),
-    };
+    }
+}
+
+pub fn load_config(config_path: Option<PathBuf>) -> anyhow::Result<Config> {
+    let (config_path, defaulted) = self::config_path(config_path);
 
     log::info!("Loading config from {}", config_path.to_string_lossy());
 
@@ -27,7 +31,9 @@ pub fn load_config(config_path: Option<PathBuf>) -> anyhow::Result<Config> {
             log::debug!("Config file exists, reading.");
             let conf = io::read_to_string(File::open(&config_path).context("Opening config file")?)
                 .context("Reading config file")?;
-            toml::from_str::<Config>(&conf).context("Reading config")
+            let config = parse_config(&conf)?;
+            config.validate().context("Validating config")?;
+            Ok(config)
         }
         Ok(false) => {
             log::debug!("Config file does not exist.");
@@ -55,3 +61,159 @@ pub fn load_config(config_path: Option<PathBuf>) -> anyhow::Result<Config> {
         }
     }
 }
+
+/// Reads the `[aliases]` first, as keys in chords can refer to them. They name keys or groups of
+/// keys, usable in chords like built-in key names.
+fn parse_config(conf: &str) -> anyhow::Result<Config> {
+    let table = toml::from_str::<toml::Table>(conf).context("Parsing config")?;
+    let aliases: BTreeMap<String, String> = match table.get("aliases") {
+        Some(aliases) => aliases.clone().try_into().context("Reading aliases")?,
+        None => BTreeMap::new(),
+    };
+    let aliases = configured_key::resolve_aliases(&aliases).context("Validating aliases")?;
+    configured_key::with_aliases(aliases, || toml::from_str::<Config>(conf))
+        .context("Reading config")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{parse_config, Backend, ValidationError};
+
+    /// Reads and validates a config with a single evdev executor holding `chords`.
+    fn validate(chords: &str) -> anyhow::Result<()> {
+        let conf =
+            format!("[[executors]]\nbackend = \"evdev\"\ndevice = \"/dev/input/event0\"\n{chords}");
+        parse_config(&conf)?.validate()?;
+        Ok(())
+    }
+
+    #[test]
+    fn chords_need_a_key_that_is_not_negated() {
+        let err = validate(
+            r#"
+            [[executors.chords]]
+            sequence = ["!shift"]
+            action = "true"
+            "#,
+        )
+        .expect_err("Only negated keys");
+        cool_asserts::assert_matches!(
+            err.downcast_ref(),
+            Some(ValidationError::Chord {
+                executor: 1,
+                chord: 1,
+                ..
+            })
+        );
+        assert!(err.to_string().starts_with("Chord #1 of executor #1"));
+
+        let err = validate(
+            r#"
+            [[executors.chords]]
+            steps = [["leftmeta"], ["!ctrl", "!alt"]]
+            action = "true"
+            "#,
+        )
+        .expect_err("A step of only negated keys");
+        cool_asserts::assert_matches!(
+            err.downcast_ref(),
+            Some(ValidationError::Chord {
+                executor: 1,
+                chord: 1,
+                ..
+            })
+        );
+
+        validate(
+            r#"
+            [[executors.chords]]
+            sequence = ["ctrl", "!shift", "k"]
+            action = "true"
+            "#,
+        )
+        .expect("Negated keys along with others");
+    }
+
+    #[test]
+    fn example_config_is_valid() {
+        let example = include_str!("../../dist/config/systemchord-example.toml");
+        parse_config(example)
+            .expect("Parsing the example")
+            .validate()
+            .expect("Validating the example");
+    }
+
+    #[test]
+    fn chord_options_fall_back_to_defaults() {
+        let config = parse_config(
+            r#"
+            [[executors]]
+            backend = "evdev"
+            device = "/dev/input/event0"
+            chords = []
+            chord_options = { tap_ms = 150 }
+            "#,
+        )
+        .expect("Only some chord options");
+        let options = config.executors[0].chord_options;
+        assert_eq!(options.tap_ms, 150);
+        assert!(options.passthrough);
+    }
+
+    #[test]
+    fn devices_can_be_matched_without_a_path() {
+        let config = parse_config(
+            r#"
+            [[executors]]
+            backend = "evdev"
+            device_name = "Keychron Q6 Max"
+            vendor = 0x3434
+            path_glob = "/dev/input/by-id/*Keychron*-event-kbd"
+            chords = []
+
+            [[executors]]
+            backend = "evdev"
+            device = "/dev/input/event0"
+            chords = []
+            "#,
+        )
+        .expect("Matchers");
+        config.validate().expect("Matchers");
+        let devices: Vec<_> = config
+            .executors
+            .into_iter()
+            .map(|executor| match executor.backend {
+                Backend::Evdev { devices, .. } => devices,
+            })
+            .collect();
+        let [matched, fixed] = &devices[..] else {
+            panic!("Two executors");
+        };
+        assert_eq!(matched.vendor, Some(0x3434));
+        assert_eq!(matched.fixed_path(), None);
+        assert_eq!(fixed.fixed_path(), Some(&"/dev/input/event0".into()));
+    }
+
+    #[test]
+    fn devices_need_a_matcher() {
+        let err = parse_config("[[executors]]\nbackend = \"evdev\"\nchords = []")
+            .expect("No matcher")
+            .validate()
+            .expect_err("No matcher");
+        cool_asserts::assert_matches!(
+            &err,
+            ValidationError::Backend { executor: 1, problem } => {
+                assert_eq!(problem, "needs a `device` or at least one device matcher");
+            }
+        );
+    }
+
+    #[test]
+    fn vendor_ids_have_16_bits() {
+        let conf = "[[executors]]\nbackend = \"evdev\"\nchords = []\nvendor = {vendor}";
+        parse_config(&conf.replace("{vendor}", "0xffff")).expect("Largest vendor");
+        let err =
+            parse_config(&conf.replace("{vendor}", "0x10000")).expect_err("Vendor out of range");
+        assert!(format!("{err:#}").contains("u16"), "{err:#}");
+    }
+}
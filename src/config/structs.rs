@@ -1,41 +1,307 @@
 use crate::config::configured_key::ConfiguredKey;
-use serde::Deserialize;
+use serde::{Deserialize, Deserializer};
 use std::{
+    collections::{BTreeMap, HashMap},
     fmt::{Display, Formatter},
     path::PathBuf,
 };
 
 const CHORD_OPTS_DEFAULT: ChordOpts = ChordOpts {
     passthrough: true,
-    exclusive: false,
+    others: Others::Allow,
+    consume: false,
+    step_timeout_ms: DEFAULT_STEP_TIMEOUT_MILLIS,
+    tap_ms: DEFAULT_TAP_MILLIS,
+    hold_ms: DEFAULT_HOLD_MILLIS,
+    retrigger: false,
+    window_ms: None,
+    ordered: false,
 };
 
+const DEFAULT_STEP_TIMEOUT_MILLIS: u64 = 1000;
+const DEFAULT_TAP_MILLIS: u64 = 200;
+const DEFAULT_HOLD_MILLIS: u64 = 500;
+const DEFAULT_AXIS_HYSTERESIS_PERCENT: u8 = 5;
+const DEFAULT_REPEAT_DELAY_MILLIS: u64 = 500;
+const DEFAULT_REPEAT_INTERVAL_MILLIS: u64 = 100;
+
 #[derive(Deserialize, Default, Debug)]
 pub struct Config {
     pub executors: Vec<Executor>,
 }
 
+impl Config {
+    /// Checks what the config format alone cannot express.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        for (executor_index, executor) in self.executors.iter().enumerate() {
+            match &executor.backend {
+                #[cfg(feature = "backend-evdev")]
+                Backend::Evdev {
+                    devices, inputs, ..
+                } => {
+                    let problem = if inputs.is_empty() {
+                        devices.problem()
+                    } else if *devices != DeviceMatcher::default() {
+                        Some("cannot match devices both directly and through `inputs`".to_string())
+                    } else {
+                        inputs.iter().find_map(|(name, devices)| {
+                            if name.is_empty() || name.contains([':', '|']) || name == "code" {
+                                Some(format!("has an input named `{name}`, but input names cannot be empty, `code` or contain `:` or `|`"))
+                            } else {
+                                let problem = devices.problem()?;
+                                Some(format!("has input `{name}`, which {problem}"))
+                            }
+                        })
+                    };
+                    if let Some(problem) = problem {
+                        return Err(ValidationError::Backend {
+                            executor: executor_index + 1,
+                            problem,
+                        });
+                    }
+                }
+            }
+            let known_mode = |mode: &String| {
+                if executor.modes.contains_key(mode) {
+                    Ok(())
+                } else {
+                    Err(ValidationError::UnknownMode {
+                        executor: executor_index + 1,
+                        mode: mode.clone(),
+                    })
+                }
+            };
+            for (name, mode) in &executor.modes {
+                for action in mode.on_enter.iter().chain(&mode.on_exit) {
+                    if action.mode_change().is_some() {
+                        return Err(ValidationError::NestedModeChange {
+                            executor: executor_index + 1,
+                            mode: name.clone(),
+                        });
+                    }
+                }
+            }
+            for (chord_index, chord) in executor.chords.iter().enumerate() {
+                let at = |problem| ValidationError::Chord {
+                    executor: executor_index + 1,
+                    chord: chord_index + 1,
+                    problem,
+                };
+                if let Some(mode) = &chord.mode {
+                    known_mode(mode)?;
+                }
+                let keys = chord.sequence.iter().chain(chord.steps.iter().flatten());
+                for input in keys.filter_map(ConfiguredKey::input) {
+                    if !executor.backend.has_input(input) {
+                        return Err(ValidationError::UnknownInput {
+                            executor: executor_index + 1,
+                            input: input.to_string(),
+                        });
+                    }
+                }
+                match chord.action.mode_change() {
+                    Some(ModeChange::Enter(mode) | ModeChange::Toggle(mode)) => known_mode(mode)?,
+                    Some(ModeChange::Exit) | None => {}
+                }
+                match (chord.sequence.is_empty(), chord.steps.is_empty()) {
+                    (true, true) => return Err(at("needs either `sequence` or `steps`")),
+                    (false, false) => {
+                        return Err(at("cannot have both `sequence` and `steps`"));
+                    }
+                    _ => {}
+                }
+                if chord.steps.iter().any(Vec::is_empty) {
+                    return Err(at("has an empty step"));
+                }
+                // negated keys alone would match before anything is pressed, and never let go
+                let positive = |keys: &[ConfiguredKey]| keys.iter().any(|key| !key.negated());
+                if !chord.sequence.is_empty() && !positive(&chord.sequence) {
+                    return Err(at("needs a key in its `sequence` that is not negated"));
+                }
+                if chord.steps.iter().any(|step| !positive(step)) {
+                    return Err(at("has a step without a key that is not negated"));
+                }
+                if !chord.steps.is_empty() && chord.trigger != Trigger::Press {
+                    return Err(at("uses `steps`, which only fire on press"));
+                }
+                if let Some(repeat) = &chord.repeat {
+                    if !chord.steps.is_empty() || chord.trigger != Trigger::Press {
+                        return Err(at(
+                            "uses `repeat`, which needs a `sequence` firing on press",
+                        ));
+                    }
+                    if chord.action.mode_change().is_some() {
+                        return Err(at("uses `repeat`, which cannot change modes"));
+                    }
+                    if repeat.interval_ms == 0 || repeat.max_running == 0 {
+                        return Err(at("uses `repeat` with an interval or `max_running` of 0"));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ValidationError {
+    #[error("Chord #{chord} of executor #{executor} {problem}")]
+    Chord {
+        executor: usize,
+        chord: usize,
+        problem: &'static str,
+    },
+    #[error("The backend of executor #{executor} {problem}")]
+    Backend { executor: usize, problem: String },
+    #[error("Executor #{executor} refers to mode `{mode}`, which is not in its `modes`")]
+    UnknownMode { executor: usize, mode: String },
+    #[error("Executor #{executor} refers to input `{input}`, which is not in its `inputs`")]
+    UnknownInput { executor: usize, input: String },
+    #[error("Mode `{mode}` of executor #{executor} cannot change modes when entered or exited")]
+    NestedModeChange { executor: usize, mode: String },
+    #[error("Alias `{alias}` cannot be empty, start with `!` or contain `:` or `|`")]
+    AliasName { alias: String },
+    #[error("Alias `{alias}` refers to `{key}`, which is neither a key nor an alias")]
+    UnknownAliasKey { alias: String, key: String },
+    #[error("Aliases refer to each other in a cycle: {cycle}")]
+    AliasCycle { cycle: String },
+}
+
 #[derive(Deserialize, Debug)]
 pub struct Executor {
+    /// Identifies the executor on the control socket.
+    pub name: Option<String>,
     #[serde(flatten)]
     pub backend: Backend,
     pub chords: Vec<Chord>,
     pub shell: Option<Vec<String>>,
+    /// Take the device exclusively and re-emit unconsumed events through a virtual device.
+    #[serde(default)]
+    pub grab: bool,
 
     #[serde(default = "ChordOpts::default")]
     pub chord_options: ChordOpts,
+    /// Named modes that chords can switch the executor into.
+    #[serde(default)]
+    pub modes: HashMap<String, Mode>,
+    /// Forget keys held for longer than this, in case their release got lost. Switches and axes
+    /// past a threshold are kept.
+    pub max_hold_ms: Option<u64>,
+    /// Ask the kernel which keys are really held before forgetting any.
+    #[serde(default)]
+    pub max_hold_check: bool,
+    /// How far, in percent, an axis has to move back past a threshold like `left_x>50%` to
+    /// release it again.
+    #[serde(default = "default_axis_hysteresis")]
+    pub axis_hysteresis_percent: u8,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Mode {
+    /// Leave the mode after this long without any of its chords firing.
+    pub timeout_ms: Option<u64>,
+    pub on_enter: Option<ChordAction>,
+    pub on_exit: Option<ChordAction>,
 }
 
 #[derive(Deserialize, Copy, Clone, Debug)]
 pub struct ChordOpts {
+    #[serde(default = "default_true")]
     pub passthrough: bool,
-    pub exclusive: bool,
+    /// What other keys may be held along with those of the chord.
+    #[serde(default, alias = "exclusive", deserialize_with = "others")]
+    pub others: Others,
+    /// Keep the key completing this chord (and its release) from reaching the desktop.
+    /// Only has an effect on executors with `grab` enabled.
+    #[serde(default)]
+    pub consume: bool,
+    /// How long a multi-step chord waits for its next step before starting over.
+    #[serde(default = "default_step_timeout")]
+    pub step_timeout_ms: u64,
+    /// Longest a chord may be held to count as a tap, and the longest gap between the taps of a
+    /// double tap.
+    #[serde(default = "default_tap")]
+    pub tap_ms: u64,
+    /// How long a chord must be held to count as a hold.
+    #[serde(default = "default_hold")]
+    pub hold_ms: u64,
+    /// Fire a chord triggered on press again on every event while it matches, instead of once
+    /// until one of its keys is released.
+    #[serde(default)]
+    pub retrigger: bool,
+    /// Only match if all keys of the chord went down within this long of each other. Such a
+    /// chord then lasts until all of its keys are up, so that it fires on `release` once the
+    /// whole stroke ends. With `consume`, its keys are held back from the desktop until the
+    /// window tells whether they make up the chord.
+    pub window_ms: Option<u64>,
+    /// Only match if the keys of the chord went down in the order they are listed.
+    #[serde(default)]
+    pub ordered: bool,
 }
 
-#[derive(Deserialize, Copy, Clone, Debug)]
+#[derive(Deserialize, Copy, Clone, Debug, Default)]
 pub struct ChordOptsChild {
     pub passthrough: Option<bool>,
-    pub exclusive: Option<bool>,
+    #[serde(default, alias = "exclusive", deserialize_with = "some_others")]
+    pub others: Option<Others>,
+    pub consume: Option<bool>,
+    pub step_timeout_ms: Option<u64>,
+    pub tap_ms: Option<u64>,
+    pub hold_ms: Option<u64>,
+    pub retrigger: Option<bool>,
+    pub window_ms: Option<u64>,
+    pub ordered: Option<bool>,
+}
+
+impl ChordOpts {
+    /// Applies the per-chord overrides on top of these options.
+    pub fn merged(&self, child: Option<ChordOptsChild>) -> ChordOpts {
+        let Some(child) = child else {
+            return *self;
+        };
+        ChordOpts {
+            passthrough: child.passthrough.unwrap_or(self.passthrough),
+            others: child.others.unwrap_or(self.others),
+            consume: child.consume.unwrap_or(self.consume),
+            step_timeout_ms: child.step_timeout_ms.unwrap_or(self.step_timeout_ms),
+            tap_ms: child.tap_ms.unwrap_or(self.tap_ms),
+            hold_ms: child.hold_ms.unwrap_or(self.hold_ms),
+            retrigger: child.retrigger.unwrap_or(self.retrigger),
+            window_ms: child.window_ms.or(self.window_ms),
+            ordered: child.ordered.unwrap_or(self.ordered),
+        }
+    }
+}
+
+/// What other keys a chord tolerates being held along with its own.
+#[derive(Deserialize, Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Others {
+    #[default]
+    Allow,
+    Forbid,
+    /// Only ctrl, alt, shift and meta may be held besides the keys of the chord.
+    ModifiersOnly,
+}
+
+/// Reads `others`, or the `exclusive` flag it replaced.
+fn others<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Others, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OthersOrExclusive {
+        Others(Others),
+        Exclusive(bool),
+    }
+
+    Ok(match OthersOrExclusive::deserialize(deserializer)? {
+        OthersOrExclusive::Others(others) => others,
+        OthersOrExclusive::Exclusive(true) => Others::Forbid,
+        OthersOrExclusive::Exclusive(false) => Others::Allow,
+    })
+}
+
+fn some_others<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Others>, D::Error> {
+    others(deserializer).map(Some)
 }
 
 impl Default for ChordOpts {
@@ -46,40 +312,243 @@ impl Default for ChordOpts {
 
 #[derive(Deserialize, Debug)]
 pub struct Chord {
+    /// Identifies the chord on the control socket.
+    pub name: Option<String>,
+    #[serde(default)]
     pub sequence: Vec<ConfiguredKey>,
+    /// Makes this a multi-step chord: each step must be matched in order, e.g. a leader key
+    /// followed by the actual binding. Mutually exclusive with `sequence`.
+    #[serde(default)]
+    pub steps: Vec<Vec<ConfiguredKey>>,
+    #[serde(default)]
+    pub trigger: Trigger,
+    /// The mode this chord is active in. Chords without a mode are only active outside of modes.
+    pub mode: Option<String>,
     pub action: ChordAction,
+    /// Run the action again and again for as long as the chord is held.
+    pub repeat: Option<Repeat>,
     pub options: Option<ChordOptsChild>,
 }
 
+/// How a held chord repeats its action. It follows its own timer rather than the autorepeat of
+/// the kernel, so any key can repeat, grabbed or not.
+#[derive(Deserialize, Copy, Clone, Debug)]
+pub struct Repeat {
+    /// How long after firing the chord first repeats.
+    #[serde(default = "default_repeat_delay")]
+    pub initial_delay_ms: u64,
+    /// How long to wait between repeats.
+    #[serde(default = "default_repeat_interval")]
+    pub interval_ms: u64,
+    /// Skip repeats while this many runs of the action have not finished yet.
+    #[serde(default = "default_max_running")]
+    pub max_running: usize,
+}
+
+/// When a matching chord fires its action.
+#[derive(Deserialize, Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Trigger {
+    /// As soon as the chord matches.
+    #[default]
+    Press,
+    /// When the chord stops matching.
+    Release,
+    /// When the chord stops matching within `tap_ms` of starting to match.
+    Tap,
+    /// Once the chord has matched for `hold_ms`.
+    Hold,
+    /// When the chord starts matching again within `tap_ms` after a tap.
+    DoubleTap,
+}
+
 #[derive(Deserialize, Debug)]
 #[serde(untagged)]
 pub enum ChordAction {
     Shell(String),
     Command(Vec<String>),
+    EnterMode {
+        enter_mode: String,
+    },
+    /// Leaves the current mode; `exit_mode = false` does nothing.
+    ExitMode {
+        exit_mode: bool,
+    },
+    ToggleMode {
+        toggle_mode: String,
+    },
 }
 
-#[derive(Deserialize, Debug)]
+/// The built-in actions that switch the executor between modes.
+#[derive(Debug, Eq, PartialEq)]
+pub enum ModeChange<'a> {
+    Enter(&'a String),
+    Exit,
+    Toggle(&'a String),
+}
+
+impl ChordAction {
+    pub fn mode_change(&self) -> Option<ModeChange<'_>> {
+        match self {
+            ChordAction::Shell(_) | ChordAction::Command(_) => None,
+            ChordAction::EnterMode { enter_mode } => Some(ModeChange::Enter(enter_mode)),
+            ChordAction::ExitMode { exit_mode: true } => Some(ModeChange::Exit),
+            ChordAction::ExitMode { exit_mode: false } => None,
+            ChordAction::ToggleMode { toggle_mode } => Some(ModeChange::Toggle(toggle_mode)),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug, Eq, PartialEq)]
 #[serde(tag = "backend", rename_all = "lowercase")]
 pub enum Backend {
     #[cfg(feature = "backend-evdev")]
     Evdev {
-        device: PathBuf,
+        #[serde(flatten)]
+        devices: DeviceMatcher,
+        /// Named groups of devices read together, so that chords can tell them apart.
+        #[serde(default)]
+        inputs: BTreeMap<String, DeviceMatcher>,
         #[serde(default = "default_true")]
         retry: bool,
     },
 }
 
+impl Backend {
+    /// Whether chord keys can be restricted to the named input.
+    pub fn has_input(&self, name: &str) -> bool {
+        match self {
+            #[cfg(feature = "backend-evdev")]
+            Backend::Evdev { inputs, .. } => inputs.contains_key(name),
+        }
+    }
+}
+
 impl Display for Backend {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             #[cfg(feature = "backend-evdev")]
-            Backend::Evdev { device, .. } => {
-                write!(f, "evdev (on {})", device.to_string_lossy())
+            Backend::Evdev {
+                devices, inputs, ..
+            } if inputs.is_empty() => {
+                write!(f, "evdev (on {devices})")
             }
+            #[cfg(feature = "backend-evdev")]
+            Backend::Evdev { inputs, .. } => {
+                let inputs = inputs
+                    .iter()
+                    .map(|(name, devices)| format!("{name}: {devices}"));
+                write!(f, "evdev (on {})", inputs.collect::<Vec<_>>().join("; "))
+            }
+        }
+    }
+}
+
+/// Picks the evdev devices to read. Every criterion given must match, and all matching devices
+/// are read as one.
+#[cfg(feature = "backend-evdev")]
+#[derive(Deserialize, Default, Clone, Debug, Eq, PartialEq)]
+pub struct DeviceMatcher {
+    /// A fixed device path, opened as is when no other criterion is given.
+    pub device: Option<PathBuf>,
+    /// Named `device_name`, since the executor itself already has a `name`.
+    #[serde(rename = "device_name")]
+    pub name: Option<String>,
+    pub name_regex: Option<String>,
+    pub vendor: Option<u16>,
+    pub product: Option<u16>,
+    pub phys: Option<String>,
+    /// Matched against the device path and its `/dev/input/by-id` and `by-path` links.
+    pub path_glob: Option<String>,
+}
+
+#[cfg(feature = "backend-evdev")]
+impl DeviceMatcher {
+    /// The device path, if nothing but a path was given.
+    pub fn fixed_path(&self) -> Option<&PathBuf> {
+        let other = DeviceMatcher {
+            device: None,
+            ..self.clone()
+        };
+        self.device
+            .as_ref()
+            .filter(|_| other == DeviceMatcher::default())
+    }
+
+    fn problem(&self) -> Option<String> {
+        if *self == DeviceMatcher::default() {
+            return Some("needs a `device` or at least one device matcher".to_string());
+        }
+        if let Some(Err(err)) = self.name_regex.as_deref().map(regex::Regex::new) {
+            return Some(format!("has an invalid `name_regex`: {err}"));
+        }
+        if let Some(Err(err)) = self.path_glob.as_deref().map(glob::Pattern::new) {
+            return Some(format!("has an invalid `path_glob`: {err}"));
         }
+        None
+    }
+}
+
+#[cfg(feature = "backend-evdev")]
+impl Display for DeviceMatcher {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if let Some(device) = self.fixed_path() {
+            return write!(f, "{}", device.to_string_lossy());
+        }
+        let mut criteria = Vec::new();
+        if let Some(device) = &self.device {
+            criteria.push(format!("path {}", device.to_string_lossy()));
+        }
+        if let Some(name) = &self.name {
+            criteria.push(format!("name `{name}`"));
+        }
+        if let Some(name_regex) = &self.name_regex {
+            criteria.push(format!("name like `{name_regex}`"));
+        }
+        if let Some(vendor) = self.vendor {
+            criteria.push(format!("vendor {vendor:04x}"));
+        }
+        if let Some(product) = self.product {
+            criteria.push(format!("product {product:04x}"));
+        }
+        if let Some(phys) = &self.phys {
+            criteria.push(format!("phys `{phys}`"));
+        }
+        if let Some(path_glob) = &self.path_glob {
+            criteria.push(format!("path like `{path_glob}`"));
+        }
+        write!(f, "devices with {}", criteria.join(", "))
     }
 }
 
 const fn default_true() -> bool {
     true
 }
+
+const fn default_axis_hysteresis() -> u8 {
+    DEFAULT_AXIS_HYSTERESIS_PERCENT
+}
+
+const fn default_step_timeout() -> u64 {
+    DEFAULT_STEP_TIMEOUT_MILLIS
+}
+
+const fn default_tap() -> u64 {
+    DEFAULT_TAP_MILLIS
+}
+
+const fn default_hold() -> u64 {
+    DEFAULT_HOLD_MILLIS
+}
+
+const fn default_repeat_delay() -> u64 {
+    DEFAULT_REPEAT_DELAY_MILLIS
+}
+
+const fn default_repeat_interval() -> u64 {
+    DEFAULT_REPEAT_INTERVAL_MILLIS
+}
+
+const fn default_max_running() -> usize {
+    1
+}
@@ -0,0 +1,49 @@
+use crate::{
+    cli::CtlCommand,
+    control::{socket_path, Request, Response},
+};
+use anyhow::{anyhow, Context};
+use std::{
+    io::{BufRead, BufReader, Write},
+    os::unix::net::UnixStream,
+};
+
+/// Sends a single command to the running daemon and prints its response.
+pub fn run(command: CtlCommand) -> anyhow::Result<()> {
+    let request = match command {
+        CtlCommand::List => Request::List,
+        CtlCommand::Pause { executor } => Request::Pause {
+            executor: executor.into(),
+        },
+        CtlCommand::Resume { executor } => Request::Resume {
+            executor: executor.into(),
+        },
+        CtlCommand::Trigger { executor, chord } => Request::Trigger {
+            executor: executor.into(),
+            chord,
+        },
+        CtlCommand::Reload => Request::Reload,
+        CtlCommand::Held { executor } => Request::Held {
+            executor: executor.into(),
+        },
+    };
+
+    let path = socket_path()?;
+    let mut stream = UnixStream::connect(&path)
+        .with_context(|| format!("Connecting to {}", path.to_string_lossy()))?;
+    writeln!(stream, "{}", serde_json::to_string(&request)?).context("Sending command")?;
+
+    let mut line = String::new();
+    BufReader::new(stream)
+        .read_line(&mut line)
+        .context("Reading response")?;
+    match serde_json::from_str::<Response>(&line).context("Parsing response")? {
+        Response::Ok { data } => {
+            if !data.is_null() {
+                println!("{}", serde_json::to_string_pretty(&data)?);
+            }
+            Ok(())
+        }
+        Response::Error { message } => Err(anyhow!(message)),
+    }
+}
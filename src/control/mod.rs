@@ -0,0 +1,186 @@
+pub mod client;
+pub mod server;
+
+use crate::APPLICATION;
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::{Display, Formatter},
+    path::PathBuf,
+};
+
+/// A command sent over the control socket as a single line of JSON. Every request is answered
+/// with exactly one [`Response`] line.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum Request {
+    /// Lists the executors and their chords.
+    List,
+    /// Stops an executor from firing chords, until it is resumed.
+    Pause {
+        executor: Selector,
+    },
+    Resume {
+        executor: Selector,
+    },
+    /// Fires a chord's action by name, as if it matched.
+    Trigger {
+        executor: Selector,
+        chord: String,
+    },
+    /// Reloads the config file.
+    Reload,
+    /// Reports the keys an executor currently sees held.
+    Held {
+        executor: Selector,
+    },
+}
+
+/// Picks an executor either by its position in the config, starting at 1 like config errors do,
+/// or by its `name`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum Selector {
+    Index(usize),
+    Name(String),
+}
+
+impl Selector {
+    /// Whether this picks the executor at `index` in the config, counted from 0.
+    pub fn selects(&self, index: usize, name: Option<&str>) -> bool {
+        match self {
+            Selector::Index(position) => index + 1 == *position,
+            Selector::Name(selected) => name == Some(selected.as_str()),
+        }
+    }
+}
+
+impl Display for Selector {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Selector::Index(index) => write!(f, "#{index}"),
+            Selector::Name(name) => write!(f, "`{name}`"),
+        }
+    }
+}
+
+impl From<String> for Selector {
+    fn from(value: String) -> Self {
+        match value.parse() {
+            Ok(index) => Selector::Index(index),
+            Err(_) => Selector::Name(value),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum Response {
+    Ok {
+        #[serde(default, skip_serializing_if = "serde_json::Value::is_null")]
+        data: serde_json::Value,
+    },
+    Error {
+        message: String,
+    },
+}
+
+impl Response {
+    pub fn ok() -> Self {
+        Response::Ok {
+            data: serde_json::Value::Null,
+        }
+    }
+
+    pub fn error(message: impl Display) -> Self {
+        Response::Error {
+            message: message.to_string(),
+        }
+    }
+}
+
+/// Where the daemon listens: `$XDG_RUNTIME_DIR/systemchord.sock`.
+pub fn socket_path() -> anyhow::Result<PathBuf> {
+    let dirs = directories::BaseDirs::new().ok_or(anyhow!("No home directory found"))?;
+    let runtime_dir = dirs
+        .runtime_dir()
+        .ok_or(anyhow!("XDG_RUNTIME_DIR is not set"))?;
+    Ok(runtime_dir.join(format!("{APPLICATION}.sock")))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::control::{Request, Response, Selector};
+    use cool_asserts::assert_matches;
+    use serde_json::json;
+
+    #[test]
+    fn selector_counts_from_one() {
+        assert!(Selector::Index(1).selects(0, None));
+        assert!(!Selector::Index(0).selects(0, None));
+        assert!(!Selector::Index(1).selects(1, Some("media")));
+        assert!(Selector::Name("media".into()).selects(3, Some("media")));
+        assert!(!Selector::Name("media".into()).selects(3, None));
+    }
+
+    #[test]
+    fn selector_from_argument() {
+        assert_matches!(Selector::from("2".to_string()), Selector::Index(2));
+        assert_matches!(Selector::from("media".to_string()), Selector::Name(name) => {
+            assert_eq!(name, "media");
+        });
+    }
+
+    #[test]
+    fn requests_round_trip() {
+        let request: Request = serde_json::from_str(r#"{"command":"pause","executor":2}"#).unwrap();
+        assert_matches!(
+            request,
+            Request::Pause {
+                executor: Selector::Index(2)
+            }
+        );
+        let request: Request =
+            serde_json::from_str(r#"{"command":"trigger","executor":"media","chord":"x"}"#)
+                .unwrap();
+        assert_matches!(&request, Request::Trigger { executor: Selector::Name(name), chord } => {
+            assert_eq!(name, "media");
+            assert_eq!(chord, "x");
+        });
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            json!({"command": "trigger", "executor": "media", "chord": "x"})
+        );
+        assert_eq!(
+            serde_json::to_value(Request::List).unwrap(),
+            json!({"command": "list"})
+        );
+        assert!(serde_json::from_str::<Request>(r#"{"command":"explode"}"#).is_err());
+    }
+
+    #[test]
+    fn responses_round_trip() {
+        assert_eq!(
+            serde_json::to_string(&Response::ok()).unwrap(),
+            r#"{"status":"ok"}"#
+        );
+        let response: Response = serde_json::from_str(r#"{"status":"ok"}"#).unwrap();
+        assert_matches!(
+            response,
+            Response::Ok {
+                data: serde_json::Value::Null
+            }
+        );
+        let response: Response =
+            serde_json::from_str(r#"{"status":"ok","data":{"held":[]}}"#).unwrap();
+        assert_matches!(response, Response::Ok { data } => {
+            assert_eq!(data, json!({"held": []}));
+        });
+        let error = serde_json::to_string(&Response::error("No executor #3")).unwrap();
+        assert_eq!(error, r#"{"status":"error","message":"No executor #3"}"#);
+        assert_matches!(
+            serde_json::from_str(&error).unwrap(),
+            Response::Error { message } => assert_eq!(message, "No executor #3")
+        );
+    }
+}
@@ -0,0 +1,109 @@
+use crate::{
+    control::{socket_path, Request, Response},
+    APPLICATION,
+};
+use anyhow::Context;
+use crossbeam_channel::{Receiver, Sender};
+use std::{
+    fs,
+    io::{BufRead, BufReader, ErrorKind, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::PathBuf,
+    thread,
+};
+
+/// A request from a client, along with where to send its response.
+pub type Incoming = (Request, Sender<Response>);
+
+/// Another instance of the daemon answers on the control socket.
+#[derive(thiserror::Error, Debug)]
+#[error("{APPLICATION} is already running, listening on {}", .0.to_string_lossy())]
+pub struct AlreadyRunning(PathBuf);
+
+/// The control socket of the daemon, removed again when dropped.
+pub struct Server {
+    pub requests: Receiver<Incoming>,
+    path: PathBuf,
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        if let Err(err) = fs::remove_file(&self.path) {
+            log::warn!("Failed to remove the control socket: {err}");
+        }
+    }
+}
+
+/// Starts listening on the control socket, handing every request to [`Server::requests`]. Fails
+/// with [`AlreadyRunning`] if another instance still listens there.
+pub fn listen() -> anyhow::Result<Server> {
+    let path = socket_path()?;
+    // only a socket nobody answers on is left over from an instance that is gone
+    if UnixStream::connect(&path).is_ok() {
+        return Err(AlreadyRunning(path).into());
+    }
+    match fs::remove_file(&path) {
+        Ok(_) => log::debug!("Removed stale control socket"),
+        Err(err) if err.kind() == ErrorKind::NotFound => {}
+        Err(err) => return Err(err).context("Removing stale control socket"),
+    }
+    let listener = UnixListener::bind(&path).context("Binding control socket")?;
+    log::info!(
+        "Listening for control commands on {}",
+        path.to_string_lossy()
+    );
+
+    let (sender, recv) = crossbeam_channel::unbounded();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let sender = sender.clone();
+                    thread::spawn(move || serve(stream, sender));
+                }
+                Err(err) => log::warn!("Failed to accept control connection: {err}"),
+            }
+        }
+    });
+    Ok(Server {
+        requests: recv,
+        path,
+    })
+}
+
+fn serve(stream: UnixStream, requests: Sender<Incoming>) {
+    let mut writer = match stream.try_clone() {
+        Ok(writer) => writer,
+        Err(err) => {
+            log::warn!("Failed to set up control connection: {err}");
+            return;
+        }
+    };
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => {
+                log::debug!("Control request: {request:?}");
+                let (reply, response) = crossbeam_channel::bounded(1);
+                if requests.send((request, reply)).is_err() {
+                    break;
+                }
+                response
+                    .recv()
+                    .unwrap_or_else(|_| Response::error("The daemon is shutting down"))
+            }
+            Err(err) => Response::error(format!("Invalid request: {err}")),
+        };
+        let written = serde_json::to_string(&response)
+            .map_err(std::io::Error::from)
+            .and_then(|json| writeln!(writer, "{json}"));
+        if written.is_err() {
+            break;
+        }
+    }
+}
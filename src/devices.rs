@@ -0,0 +1,52 @@
+use crate::backend::{self, DeviceInfo};
+use anyhow::Context;
+
+/// Up to how many keys are listed by name rather than counted.
+const SHOWN_KEYS: usize = 8;
+
+/// Prints the input devices, as text or as JSON for scripts.
+pub fn list(json: bool) -> anyhow::Result<()> {
+    let devices = backend::list_devices().context("Listing input devices in /dev/input")?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&devices)?);
+        return Ok(());
+    }
+    for device in devices {
+        print_device(&device);
+    }
+    Ok(())
+}
+
+fn print_device(device: &DeviceInfo) {
+    println!(
+        "{}: {}",
+        device.path.to_string_lossy(),
+        device.name.as_deref().unwrap_or("(unnamed)")
+    );
+    if let Some(error) = &device.error {
+        println!("    cannot open: {error}");
+    }
+    if let (Some(vendor), Some(product), Some(version)) =
+        (device.vendor, device.product, device.version)
+    {
+        println!("    id: vendor {vendor:04x}, product {product:04x}, version {version:04x}");
+    }
+    if let Some(phys) = &device.phys {
+        println!("    phys: {phys}");
+    }
+    if let Some(uniq) = &device.uniq {
+        println!("    uniq: {uniq}");
+    }
+    for link in device.by_id.iter().chain(&device.by_path) {
+        println!("    link: {}", link.to_string_lossy());
+    }
+    match device.keys.as_deref() {
+        Some([]) => println!("    reports no keys usable in chords"),
+        // few keys usually mean a power button, a lid or the like rather than a keyboard
+        Some(keys) if keys.len() <= SHOWN_KEYS => {
+            println!("    reports keys usable in chords: {}", keys.join(", "));
+        }
+        Some(keys) => println!("    reports {} keys usable in chords", keys.len()),
+        None => {}
+    }
+}
@@ -1,5 +1,11 @@
 use crate::config::ChordAction;
-use std::process::Command;
+use std::{
+    process::Command,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
 
 fn action_to_command(chord_action: &ChordAction, shell: Option<&Vec<String>>) -> Option<Command> {
     Some(match chord_action {
@@ -26,21 +32,41 @@ fn action_to_command(chord_action: &ChordAction, shell: Option<&Vec<String>>) ->
             cmd.args(&command[1..]);
             cmd
         }
+        // mode changes are applied by the chord handler, there is nothing to run
+        ChordAction::EnterMode { .. }
+        | ChordAction::ExitMode { .. }
+        | ChordAction::ToggleMode { .. } => {
+            return None;
+        }
     })
 }
 
 pub fn exec_action(chord_action: &ChordAction, shell: Option<&Vec<String>>) {
+    exec_counted(chord_action, shell, None);
+}
+
+/// Runs the action like [`exec_action`], counting it in `running` until it finishes.
+pub fn exec_counted(
+    chord_action: &ChordAction,
+    shell: Option<&Vec<String>>,
+    running: Option<Arc<AtomicUsize>>,
+) {
     let Some(mut cmd) = action_to_command(chord_action, shell) else {
         return;
     };
+    if let Some(running) = &running {
+        running.fetch_add(1, Ordering::Relaxed);
+    }
     std::thread::spawn(move || {
-        let Ok(mut child) = cmd.spawn() else {
+        if let Ok(mut child) = cmd.spawn() {
+            if child.wait().is_err() {
+                log::error!("Failed to wait child process");
+            }
+        } else {
             log::error!("Failed to spawn child command: {:?}", cmd);
-            return;
-        };
-        let Ok(_) = child.wait() else {
-            log::error!("Failed to wait child process");
-            return;
-        };
+        }
+        if let Some(running) = running {
+            running.fetch_sub(1, Ordering::Relaxed);
+        }
     });
 }
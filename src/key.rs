@@ -1,561 +1,1068 @@
 use derive_more::Display;
-use std::{collections::HashMap, sync::OnceLock};
+use std::{collections::HashMap, fmt, str::FromStr, sync::OnceLock};
 
-#[allow(clippy::upper_case_acronyms)]
-#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display)]
-pub enum Key {
-    Esc,
-    F1,
-    F2,
-    F3,
-    F4,
-    F5,
-    F6,
-    F7,
-    F8,
-    F9,
-    F10,
-    F11,
-    F12,
-    F13,
-    F14,
-    F15,
-    F16,
-    F17,
-    F18,
-    F19,
-    F20,
-    F21,
-    F22,
-    F23,
-    F24,
-    LeftCtrl,
-    RightCtrl,
-    LeftAlt,
-    RightAlt,
-    LeftShift,
-    RightShift,
-    LeftMeta,
-    RightMeta,
-    N1,
-    N2,
-    N3,
-    N4,
-    N5,
-    N6,
-    N7,
-    N8,
-    N9,
-    N0,
-    Q,
-    W,
-    E,
-    R,
-    T,
-    Y,
-    U,
-    I,
-    O,
-    P,
-    A,
-    S,
-    D,
-    F,
-    G,
-    H,
-    J,
-    K,
-    L,
-    Z,
-    X,
-    C,
-    V,
-    B,
-    N,
-    M,
-    Minus,
-    Equal,
-    Backspace,
-    Tab,
-    LeftBracket,
-    RightBracket,
-    Enter,
-    Semicolon,
-    Apostrophe,
-    Grave,
-    Backslash,
-    Comma,
-    Dot,
-    Slash,
-    Space,
-    CapsLock,
-    NumLock,
-    ScrollLock,
-    KP0,
-    KP1,
-    KP2,
-    KP3,
-    KP4,
-    KP5,
-    KP6,
-    KP7,
-    KP8,
-    KP9,
-    KPEnter,
-    KPDot,
-    KPMinus,
-    KPPlus,
-    KPAsterisk,
-    KPSlash,
-    KPJPComma,
-    Zenkakuhankaku,
-    Ro,
-    Katakana,
-    Hiragana,
-    Henkan,
-    Katakanahiragana,
-    Muhenkan,
-    SysRq,
-    Linefeed,
-    Home,
-    Up,
-    Pageup,
-    Left,
-    Right,
-    End,
-    Down,
-    Pagedown,
-    Insert,
-    Delete,
-    Macro,
-    Mute,
-    VolumeDown,
-    VolumeUp,
-    Power,
-    KPEqual,
-    KPPlusMinus,
-    Pause,
-    Scale,
-    KPComma,
-    Hangeul,
-    Hanja,
-    Yen,
-    Compose,
-    Again,
-    Props,
-    Undo,
-    Front,
-    Copy,
-    Open,
-    Paste,
-    Find,
-    Cut,
-    Help,
-    Menu,
-    Calc,
-    Setup,
-    Sleep,
-    Wakeup,
-    File,
-    SendFile,
-    DeleteFile,
-    Xfer,
-    Prog1,
-    Prog2,
-    WWW,
-    MSDOS,
-    ScreenLock,
-    RotateDisplay,
-    CycleWindows,
-    Mail,
-    Bookmarks,
-    Computer,
-    Back,
-    Forward,
-    CloseCD,
-    EjectCD,
-    EjectCloseCD,
-    NextSong,
-    PlayPause,
-    PreviousSong,
-    StopCD,
-    Record,
-    Rewind,
-    Phone,
-    Iso,
-    Config,
-    Homepage,
-    Refresh,
-    Exit,
-    Move,
-    Edit,
-    ScrollUp,
-    ScrollDown,
-    KPLeftParen,
-    KPRightParen,
-    New,
-    Redo,
-    PlayCD,
-    PauseCD,
-    Prog3,
-    Prog4,
-    AllApplications,
-    Suspend,
-    Close,
-    Play,
-    FastForward,
-    BassBoost,
-    Print,
-    Hp,
-    Camera,
-    Sound,
-    Question,
-    Email,
-    Chat,
-    Search,
-    Connect,
-    Finance,
-    Sport,
-    Shop,
-    AltErase,
-    Cancel,
-    BrightnessDown,
-    BrightnessUp,
-    Media,
-    SwitchVideoMode,
-    Send,
-    Reply,
-    ForwardMail,
-    Save,
-    Documents,
-    Battery,
-    Bluetooth,
-    WLAN,
-    UWB,
-    VideoNext,
-    VideoPrev,
-    BrightnessCycle,
-    BrightnessAuto,
-    DisplayOff,
-    WWAN,
-    RFKill,
-    MicMute,
+/// Declares every named key along with how the backends report it, generating the [`Key`] enum,
+/// its names and the lookups from evdev codes. Each key lists the name it is printed with first,
+/// then its aliases.
+macro_rules! keys {
+    (
+        keys { $($key:ident = $key_code:literal => $($key_name:literal)|+,)* }
+        switches { $($switch:ident = $switch_code:literal => $($switch_name:literal)|+,)* }
+        wheels { $($wheel:ident => $($wheel_name:literal)|+,)* }
+        hats { $($hat:ident => $($hat_name:literal)|+,)* }
+    ) => {
+        #[allow(clippy::upper_case_acronyms)]
+        #[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
+        pub enum Key {
+            $($key,)*
+            $($switch,)*
+            $($wheel,)*
+            $($hat,)*
+            /// An absolute axis beyond a threshold, like `left_trigger>50%`.
+            Threshold(Threshold),
+            /// A key without a name, by its raw code, written as `code:NNN` or `0xNN`.
+            Raw(u16),
+        }
+
+        impl Key {
+            /// The keys and buttons evdev reports as `EV_KEY`, with their codes.
+            pub const KEYS: &'static [(Key, u16)] = &[$((Key::$key, $key_code),)*];
+            /// The switches evdev reports as `EV_SW`, held while on, with their codes.
+            pub const SWITCHES: &'static [(Key, u16)] = &[$((Key::$switch, $switch_code),)*];
+            /// Wheel movements, which have no code of their own and are pressed and released at
+            /// once.
+            pub const WHEELS: &'static [Key] = &[$(Key::$wheel,)*];
+            /// The directions a gamepad hat points in, which have no code of their own and are
+            /// held while it points that way.
+            pub const HATS: &'static [Key] = &[$(Key::$hat,)*];
+
+            /// The key with the given evdev key code, or failing that the raw code.
+            pub fn from_code(code: u16) -> Key {
+                match code {
+                    $($key_code => Key::$key,)*
+                    _ => Key::Raw(code),
+                }
+            }
+
+            /// The key held while the switch with the given evdev code is on.
+            pub fn from_switch(code: u16) -> Option<Key> {
+                match code {
+                    $($switch_code => Some(Key::$switch),)*
+                    _ => None,
+                }
+            }
+
+            /// The names [`get_key_for_name`] accepts for this key, the first being how it is
+            /// printed. Empty for thresholds and raw codes, which have a syntax of their own.
+            pub fn names(&self) -> &'static [&'static str] {
+                match self {
+                    $(Key::$key => &[$($key_name),+],)*
+                    $(Key::$switch => &[$($switch_name),+],)*
+                    $(Key::$wheel => &[$($wheel_name),+],)*
+                    $(Key::$hat => &[$($hat_name),+],)*
+                    Key::Threshold(_) | Key::Raw(_) => &[],
+                }
+            }
+        }
+    };
+}
+
+keys! {
+    keys {
+        Esc = 1 => "esc",
+        N1 = 2 => "n1" | "1",
+        N2 = 3 => "n2" | "2",
+        N3 = 4 => "n3" | "3",
+        N4 = 5 => "n4" | "4",
+        N5 = 6 => "n5" | "5",
+        N6 = 7 => "n6" | "6",
+        N7 = 8 => "n7" | "7",
+        N8 = 9 => "n8" | "8",
+        N9 = 10 => "n9" | "9",
+        N0 = 11 => "n0" | "0",
+        Minus = 12 => "minus" | "dash",
+        Equal = 13 => "equal" | "plus",
+        Backspace = 14 => "backspace",
+        Tab = 15 => "tab",
+        Q = 16 => "q",
+        W = 17 => "w",
+        E = 18 => "e",
+        R = 19 => "r",
+        T = 20 => "t",
+        Y = 21 => "y",
+        U = 22 => "u",
+        I = 23 => "i",
+        O = 24 => "o",
+        P = 25 => "p",
+        LeftBracket = 26 => "leftbracket" | "leftbrace",
+        RightBracket = 27 => "rightbracket" | "rightbrace",
+        Enter = 28 => "enter",
+        LeftCtrl = 29 => "leftctrl",
+        A = 30 => "a",
+        S = 31 => "s",
+        D = 32 => "d",
+        F = 33 => "f",
+        G = 34 => "g",
+        H = 35 => "h",
+        J = 36 => "j",
+        K = 37 => "k",
+        L = 38 => "l",
+        Semicolon = 39 => "semicolon",
+        Apostrophe = 40 => "apostrophe",
+        Grave = 41 => "grave" | "tilde",
+        LeftShift = 42 => "leftshift",
+        Backslash = 43 => "backslash",
+        Z = 44 => "z",
+        X = 45 => "x",
+        C = 46 => "c",
+        V = 47 => "v",
+        B = 48 => "b",
+        N = 49 => "n",
+        M = 50 => "m",
+        Comma = 51 => "comma",
+        Dot = 52 => "dot",
+        Slash = 53 => "slash",
+        RightShift = 54 => "rightshift",
+        KPAsterisk = 55 => "kpasterisk" | "npasterisk" | "numpadasterisk",
+        LeftAlt = 56 => "leftalt",
+        Space = 57 => "space",
+        CapsLock = 58 => "capslock",
+        F1 = 59 => "f1",
+        F2 = 60 => "f2",
+        F3 = 61 => "f3",
+        F4 = 62 => "f4",
+        F5 = 63 => "f5",
+        F6 = 64 => "f6",
+        F7 = 65 => "f7",
+        F8 = 66 => "f8",
+        F9 = 67 => "f9",
+        F10 = 68 => "f10",
+        NumLock = 69 => "numlock",
+        ScrollLock = 70 => "scrolllock",
+        KP7 = 71 => "kp7" | "np7" | "numpad7",
+        KP8 = 72 => "kp8" | "np8" | "numpad8",
+        KP9 = 73 => "kp9" | "np9" | "numpad9",
+        KPMinus = 74 => "kpminus" | "npminus" | "numpadminus",
+        KP4 = 75 => "kp4" | "np4" | "numpad4",
+        KP5 = 76 => "kp5" | "np5" | "numpad5",
+        KP6 = 77 => "kp6" | "np6" | "numpad6",
+        KPPlus = 78 => "kpplus" | "npplus" | "numpadplus",
+        KP1 = 79 => "kp1" | "np1" | "numpad1",
+        KP2 = 80 => "kp2" | "np2" | "numpad2",
+        KP3 = 81 => "kp3" | "np3" | "numpad3",
+        KP0 = 82 => "kp0" | "np0" | "numpad0",
+        KPDot = 83 => "kpdot" | "npdot" | "numpaddot",
+        Zenkakuhankaku = 85 => "zenkakuhankaku",
+        K102nd = 86 => "102nd",
+        F11 = 87 => "f11",
+        F12 = 88 => "f12",
+        Ro = 89 => "ro",
+        Katakana = 90 => "katakana",
+        Hiragana = 91 => "hiragana",
+        Henkan = 92 => "henkan",
+        Katakanahiragana = 93 => "katakanahiragana",
+        Muhenkan = 94 => "muhenkan",
+        KPJPComma = 95 => "kpjpcomma" | "npjpcomma" | "numpadjpcomma",
+        KPEnter = 96 => "kpenter" | "npenter" | "numpadenter",
+        RightCtrl = 97 => "rightctrl",
+        KPSlash = 98 => "kpslash" | "npslash" | "numpadslash",
+        SysRq = 99 => "sysrq",
+        RightAlt = 100 => "rightalt",
+        Linefeed = 101 => "linefeed",
+        Home = 102 => "home",
+        Up = 103 => "up" | "uparrow",
+        Pageup = 104 => "pageup",
+        Left = 105 => "left" | "leftarrow",
+        Right = 106 => "right" | "rightarrow",
+        End = 107 => "end",
+        Down = 108 => "down" | "downarrow",
+        Pagedown = 109 => "pagedown",
+        Insert = 110 => "insert",
+        Delete = 111 => "delete",
+        Macro = 112 => "macro",
+        Mute = 113 => "mute",
+        VolumeDown = 114 => "volumedown",
+        VolumeUp = 115 => "volumeup",
+        Power = 116 => "power",
+        KPEqual = 117 => "kpequal",
+        KPPlusMinus = 118 => "kpplusminus",
+        Pause = 119 => "pause",
+        Scale = 120 => "scale",
+        KPComma = 121 => "kpcomma",
+        Hangeul = 122 => "hangeul",
+        Hanja = 123 => "hanja",
+        Yen = 124 => "yen",
+        LeftMeta = 125 => "leftmeta",
+        RightMeta = 126 => "rightmeta",
+        Compose = 127 => "compose",
+        Stop = 128 => "stop",
+        Again = 129 => "again",
+        Props = 130 => "props",
+        Undo = 131 => "undo",
+        Front = 132 => "front",
+        Copy = 133 => "copy",
+        Open = 134 => "open",
+        Paste = 135 => "paste",
+        Find = 136 => "find",
+        Cut = 137 => "cut",
+        Help = 138 => "help",
+        Menu = 139 => "menu",
+        Calc = 140 => "calc" | "calculator",
+        Setup = 141 => "setup",
+        Sleep = 142 => "sleep",
+        Wakeup = 143 => "wakeup",
+        File = 144 => "file",
+        SendFile = 145 => "sendfile",
+        DeleteFile = 146 => "deletefile",
+        Xfer = 147 => "xfer",
+        Prog1 = 148 => "prog1",
+        Prog2 = 149 => "prog2",
+        WWW = 150 => "www",
+        MSDOS = 151 => "msdos",
+        ScreenLock = 152 => "screenlock" | "coffee",
+        RotateDisplay = 153 => "rotatedisplay" | "direction",
+        CycleWindows = 154 => "cyclewindows",
+        Mail = 155 => "mail",
+        Bookmarks = 156 => "bookmarks",
+        Computer = 157 => "computer",
+        Back = 158 => "back",
+        Forward = 159 => "forward",
+        CloseCD = 160 => "closecd",
+        EjectCD = 161 => "ejectcd",
+        EjectCloseCD = 162 => "ejectclosecd",
+        NextSong = 163 => "nextsong",
+        PlayPause = 164 => "playpause",
+        PreviousSong = 165 => "previoussong",
+        StopCD = 166 => "stopcd",
+        Record = 167 => "record",
+        Rewind = 168 => "rewind",
+        Phone = 169 => "phone",
+        Iso = 170 => "iso",
+        Config = 171 => "config",
+        Homepage = 172 => "homepage",
+        Refresh = 173 => "refresh",
+        Exit = 174 => "exit",
+        Move = 175 => "move",
+        Edit = 176 => "edit",
+        ScrollUp = 177 => "scrollup",
+        ScrollDown = 178 => "scrolldown",
+        KPLeftParen = 179 => "kpleftparen",
+        KPRightParen = 180 => "kprightparen",
+        New = 181 => "new",
+        Redo = 182 => "redo",
+        F13 = 183 => "f13",
+        F14 = 184 => "f14",
+        F15 = 185 => "f15",
+        F16 = 186 => "f16",
+        F17 = 187 => "f17",
+        F18 = 188 => "f18",
+        F19 = 189 => "f19",
+        F20 = 190 => "f20",
+        F21 = 191 => "f21",
+        F22 = 192 => "f22",
+        F23 = 193 => "f23",
+        F24 = 194 => "f24",
+        PlayCD = 200 => "playcd",
+        PauseCD = 201 => "pausecd",
+        Prog3 = 202 => "prog3",
+        Prog4 = 203 => "prog4",
+        AllApplications = 204 => "allapplications" | "dashboard",
+        Suspend = 205 => "suspend",
+        Close = 206 => "close",
+        Play = 207 => "play",
+        FastForward = 208 => "fastforward",
+        BassBoost = 209 => "bassboost",
+        Print = 210 => "print",
+        Hp = 211 => "hp",
+        Camera = 212 => "camera",
+        Sound = 213 => "sound",
+        Question = 214 => "question",
+        Email = 215 => "email",
+        Chat = 216 => "chat",
+        Search = 217 => "search",
+        Connect = 218 => "connect",
+        Finance = 219 => "finance",
+        Sport = 220 => "sport",
+        Shop = 221 => "shop",
+        AltErase = 222 => "alterase",
+        Cancel = 223 => "cancel",
+        BrightnessDown = 224 => "brightnessdown",
+        BrightnessUp = 225 => "brightnessup",
+        Media = 226 => "media",
+        SwitchVideoMode = 227 => "switchvideomode",
+        Kbdillumtoggle = 228 => "kbdillumtoggle",
+        Kbdillumdown = 229 => "kbdillumdown",
+        Kbdillumup = 230 => "kbdillumup",
+        Send = 231 => "send",
+        Reply = 232 => "reply",
+        ForwardMail = 233 => "forwardmail",
+        Save = 234 => "save",
+        Documents = 235 => "documents",
+        Battery = 236 => "battery",
+        Bluetooth = 237 => "bluetooth",
+        WLAN = 238 => "wlan",
+        UWB = 239 => "uwb",
+        Unknown = 240 => "unknown",
+        VideoNext = 241 => "videonext",
+        VideoPrev = 242 => "videoprev",
+        BrightnessCycle = 243 => "brightnesscycle",
+        BrightnessAuto = 244 => "brightnessauto",
+        DisplayOff = 245 => "displayoff",
+        WWAN = 246 => "wwan",
+        RFKill = 247 => "rfkill",
+        MicMute = 248 => "micmute",
+        Btn0 = 0x100 => "btn_0",
+        Btn1 = 0x101 => "btn_1",
+        Btn2 = 0x102 => "btn_2",
+        Btn3 = 0x103 => "btn_3",
+        Btn4 = 0x104 => "btn_4",
+        Btn5 = 0x105 => "btn_5",
+        Btn6 = 0x106 => "btn_6",
+        Btn7 = 0x107 => "btn_7",
+        Btn8 = 0x108 => "btn_8",
+        Btn9 = 0x109 => "btn_9",
+        MouseLeft = 0x110 => "mouse_left",
+        MouseRight = 0x111 => "mouse_right",
+        MouseMiddle = 0x112 => "mouse_middle",
+        MouseSide = 0x113 => "mouse_side",
+        MouseExtra = 0x114 => "mouse_extra",
+        MouseForward = 0x115 => "mouse_forward",
+        MouseBack = 0x116 => "mouse_back",
+        MouseTask = 0x117 => "mouse_task",
+        JoyTrigger = 0x120 => "joy_trigger",
+        JoyThumb = 0x121 => "joy_thumb",
+        JoyThumb2 = 0x122 => "joy_thumb2",
+        JoyTop = 0x123 => "joy_top",
+        JoyTop2 = 0x124 => "joy_top2",
+        JoyPinkie = 0x125 => "joy_pinkie",
+        JoyBase = 0x126 => "joy_base",
+        JoyBase2 = 0x127 => "joy_base2",
+        JoyBase3 = 0x128 => "joy_base3",
+        JoyBase4 = 0x129 => "joy_base4",
+        JoyBase5 = 0x12a => "joy_base5",
+        JoyBase6 = 0x12b => "joy_base6",
+        JoyDead = 0x12f => "joy_dead",
+        PadSouth = 0x130 => "pad_south",
+        PadEast = 0x131 => "pad_east",
+        PadC = 0x132 => "pad_c",
+        PadNorth = 0x133 => "pad_north",
+        PadWest = 0x134 => "pad_west",
+        PadZ = 0x135 => "pad_z",
+        PadTl = 0x136 => "pad_tl",
+        PadTr = 0x137 => "pad_tr",
+        PadTl2 = 0x138 => "pad_tl2",
+        PadTr2 = 0x139 => "pad_tr2",
+        PadSelect = 0x13a => "pad_select",
+        PadStart = 0x13b => "pad_start",
+        PadMode = 0x13c => "pad_mode",
+        PadThumbL = 0x13d => "pad_thumbl",
+        PadThumbR = 0x13e => "pad_thumbr",
+        BtnToolPen = 0x140 => "btn_tool_pen",
+        BtnToolRubber = 0x141 => "btn_tool_rubber",
+        BtnToolBrush = 0x142 => "btn_tool_brush",
+        BtnToolPencil = 0x143 => "btn_tool_pencil",
+        BtnToolAirbrush = 0x144 => "btn_tool_airbrush",
+        BtnToolFinger = 0x145 => "btn_tool_finger",
+        BtnToolMouse = 0x146 => "btn_tool_mouse",
+        BtnToolLens = 0x147 => "btn_tool_lens",
+        BtnToolQuinttap = 0x148 => "btn_tool_quinttap",
+        BtnTouch = 0x14a => "btn_touch",
+        BtnStylus = 0x14b => "btn_stylus",
+        BtnStylus2 = 0x14c => "btn_stylus2",
+        BtnToolDoubletap = 0x14d => "btn_tool_doubletap",
+        BtnToolTripletap = 0x14e => "btn_tool_tripletap",
+        BtnToolQuadtap = 0x14f => "btn_tool_quadtap",
+        BtnGearDown = 0x150 => "btn_gear_down",
+        BtnGearUp = 0x151 => "btn_gear_up",
+        Ok = 0x160 => "ok",
+        Select = 0x161 => "select",
+        Goto = 0x162 => "goto",
+        Clear = 0x163 => "clear",
+        Power2 = 0x164 => "power2",
+        Option = 0x165 => "option",
+        Info = 0x166 => "info",
+        Time = 0x167 => "time",
+        Vendor = 0x168 => "vendor",
+        Archive = 0x169 => "archive",
+        Program = 0x16a => "program",
+        Channel = 0x16b => "channel",
+        Favorites = 0x16c => "favorites",
+        Epg = 0x16d => "epg",
+        Pvr = 0x16e => "pvr",
+        Mhp = 0x16f => "mhp",
+        Language = 0x170 => "language",
+        Title = 0x171 => "title",
+        Subtitle = 0x172 => "subtitle",
+        Angle = 0x173 => "angle",
+        Zoom = 0x174 => "zoom",
+        Mode = 0x175 => "mode",
+        Keyboard = 0x176 => "keyboard",
+        Screen = 0x177 => "screen",
+        Pc = 0x178 => "pc",
+        Tv = 0x179 => "tv",
+        Tv2 = 0x17a => "tv2",
+        Vcr = 0x17b => "vcr",
+        Vcr2 = 0x17c => "vcr2",
+        Sat = 0x17d => "sat",
+        Sat2 = 0x17e => "sat2",
+        Cd = 0x17f => "cd",
+        Tape = 0x180 => "tape",
+        Radio = 0x181 => "radio",
+        Tuner = 0x182 => "tuner",
+        Player = 0x183 => "player",
+        Text = 0x184 => "text",
+        Dvd = 0x185 => "dvd",
+        Aux = 0x186 => "aux",
+        Mp3 = 0x187 => "mp3",
+        Audio = 0x188 => "audio",
+        Video = 0x189 => "video",
+        Directory = 0x18a => "directory",
+        List = 0x18b => "list",
+        Memo = 0x18c => "memo",
+        Calendar = 0x18d => "calendar",
+        Red = 0x18e => "red",
+        Green = 0x18f => "green",
+        Yellow = 0x190 => "yellow",
+        Blue = 0x191 => "blue",
+        Channelup = 0x192 => "channelup",
+        Channeldown = 0x193 => "channeldown",
+        First = 0x194 => "first",
+        Last = 0x195 => "last",
+        Ab = 0x196 => "ab",
+        Next = 0x197 => "next",
+        Restart = 0x198 => "restart",
+        Slow = 0x199 => "slow",
+        Shuffle = 0x19a => "shuffle",
+        Break = 0x19b => "break",
+        Previous = 0x19c => "previous",
+        Digits = 0x19d => "digits",
+        Teen = 0x19e => "teen",
+        Twen = 0x19f => "twen",
+        Videophone = 0x1a0 => "videophone",
+        Games = 0x1a1 => "games",
+        Zoomin = 0x1a2 => "zoomin",
+        Zoomout = 0x1a3 => "zoomout",
+        Zoomreset = 0x1a4 => "zoomreset",
+        Wordprocessor = 0x1a5 => "wordprocessor",
+        Editor = 0x1a6 => "editor",
+        Spreadsheet = 0x1a7 => "spreadsheet",
+        Graphicseditor = 0x1a8 => "graphicseditor",
+        Presentation = 0x1a9 => "presentation",
+        Database = 0x1aa => "database",
+        News = 0x1ab => "news",
+        Voicemail = 0x1ac => "voicemail",
+        Addressbook = 0x1ad => "addressbook",
+        Messenger = 0x1ae => "messenger",
+        Displaytoggle = 0x1af => "displaytoggle",
+        Spellcheck = 0x1b0 => "spellcheck",
+        Logoff = 0x1b1 => "logoff",
+        Dollar = 0x1b2 => "dollar",
+        Euro = 0x1b3 => "euro",
+        Frameback = 0x1b4 => "frameback",
+        Frameforward = 0x1b5 => "frameforward",
+        ContextMenu = 0x1b6 => "contextmenu",
+        MediaRepeat = 0x1b7 => "mediarepeat",
+        K10channelsup = 0x1b8 => "10channelsup",
+        K10channelsdown = 0x1b9 => "10channelsdown",
+        Images = 0x1ba => "images",
+        DelEol = 0x1c0 => "deleol",
+        DelEos = 0x1c1 => "deleos",
+        InsLine = 0x1c2 => "insline",
+        DelLine = 0x1c3 => "delline",
+        Fn = 0x1d0 => "fnkey",
+        FnEsc = 0x1d1 => "fnesc",
+        FnF1 = 0x1d2 => "fnf1",
+        FnF2 = 0x1d3 => "fnf2",
+        FnF3 = 0x1d4 => "fnf3",
+        FnF4 = 0x1d5 => "fnf4",
+        FnF5 = 0x1d6 => "fnf5",
+        FnF6 = 0x1d7 => "fnf6",
+        FnF7 = 0x1d8 => "fnf7",
+        FnF8 = 0x1d9 => "fnf8",
+        FnF9 = 0x1da => "fnf9",
+        FnF10 = 0x1db => "fnf10",
+        FnF11 = 0x1dc => "fnf11",
+        FnF12 = 0x1dd => "fnf12",
+        Fn1 = 0x1de => "fn1",
+        Fn2 = 0x1df => "fn2",
+        FnD = 0x1e0 => "fnd",
+        FnE = 0x1e1 => "fne",
+        FnF = 0x1e2 => "fnf",
+        FnS = 0x1e3 => "fns",
+        FnB = 0x1e4 => "fnb",
+        BrlDot1 = 0x1f1 => "brldot1",
+        BrlDot2 = 0x1f2 => "brldot2",
+        BrlDot3 = 0x1f3 => "brldot3",
+        BrlDot4 = 0x1f4 => "brldot4",
+        BrlDot5 = 0x1f5 => "brldot5",
+        BrlDot6 = 0x1f6 => "brldot6",
+        BrlDot7 = 0x1f7 => "brldot7",
+        BrlDot8 = 0x1f8 => "brldot8",
+        BrlDot9 = 0x1f9 => "brldot9",
+        BrlDot10 = 0x1fa => "brldot10",
+        Numeric0 = 0x200 => "numeric0",
+        Numeric1 = 0x201 => "numeric1",
+        Numeric2 = 0x202 => "numeric2",
+        Numeric3 = 0x203 => "numeric3",
+        Numeric4 = 0x204 => "numeric4",
+        Numeric5 = 0x205 => "numeric5",
+        Numeric6 = 0x206 => "numeric6",
+        Numeric7 = 0x207 => "numeric7",
+        Numeric8 = 0x208 => "numeric8",
+        Numeric9 = 0x209 => "numeric9",
+        NumericStar = 0x20a => "numericstar",
+        NumericPound = 0x20b => "numericpound",
+        NumericA = 0x20c => "numerica",
+        NumericB = 0x20d => "numericb",
+        NumericC = 0x20e => "numericc",
+        NumericD = 0x20f => "numericd",
+        CameraFocus = 0x210 => "camerafocus",
+        WpsButton = 0x211 => "wpsbutton",
+        TouchpadToggle = 0x212 => "touchpadtoggle",
+        TouchpadOn = 0x213 => "touchpadon",
+        TouchpadOff = 0x214 => "touchpadoff",
+        CameraZoomin = 0x215 => "camerazoomin",
+        CameraZoomout = 0x216 => "camerazoomout",
+        CameraUp = 0x217 => "cameraup",
+        CameraDown = 0x218 => "cameradown",
+        CameraLeft = 0x219 => "cameraleft",
+        CameraRight = 0x21a => "cameraright",
+        AttendantOn = 0x21b => "attendanton",
+        AttendantOff = 0x21c => "attendantoff",
+        AttendantToggle = 0x21d => "attendanttoggle",
+        LightsToggle = 0x21e => "lightstoggle",
+        DpadUp = 0x220 => "dpad_up",
+        DpadDown = 0x221 => "dpad_down",
+        DpadLeft = 0x222 => "dpad_left",
+        DpadRight = 0x223 => "dpad_right",
+        AlsToggle = 0x230 => "alstoggle",
+        Buttonconfig = 0x240 => "buttonconfig",
+        Taskmanager = 0x241 => "taskmanager",
+        Journal = 0x242 => "journal",
+        Controlpanel = 0x243 => "controlpanel",
+        Appselect = 0x244 => "appselect",
+        Screensaver = 0x245 => "screensaver",
+        Voicecommand = 0x246 => "voicecommand",
+        Assistant = 0x247 => "assistant",
+        KbdLayoutNext = 0x248 => "kbdlayoutnext",
+        BrightnessMin = 0x250 => "brightnessmin",
+        BrightnessMax = 0x251 => "brightnessmax",
+        KbdinputassistPrev = 0x260 => "kbdinputassistprev",
+        KbdinputassistNext = 0x261 => "kbdinputassistnext",
+        KbdinputassistPrevgroup = 0x262 => "kbdinputassistprevgroup",
+        KbdinputassistNextgroup = 0x263 => "kbdinputassistnextgroup",
+        KbdinputassistAccept = 0x264 => "kbdinputassistaccept",
+        KbdinputassistCancel = 0x265 => "kbdinputassistcancel",
+        RightUp = 0x266 => "rightup",
+        RightDown = 0x267 => "rightdown",
+        LeftUp = 0x268 => "leftup",
+        LeftDown = 0x269 => "leftdown",
+        RootMenu = 0x26a => "rootmenu",
+        MediaTopMenu = 0x26b => "mediatopmenu",
+        Numeric11 = 0x26c => "numeric11",
+        Numeric12 = 0x26d => "numeric12",
+        AudioDesc = 0x26e => "audiodesc",
+        K3dMode = 0x26f => "3dmode",
+        NextFavorite = 0x270 => "nextfavorite",
+        StopRecord = 0x271 => "stoprecord",
+        PauseRecord = 0x272 => "pauserecord",
+        Vod = 0x273 => "vod",
+        Unmute = 0x274 => "unmute",
+        Fastreverse = 0x275 => "fastreverse",
+        Slowreverse = 0x276 => "slowreverse",
+        Data = 0x277 => "data",
+        OnscreenKeyboard = 0x278 => "onscreenkeyboard",
+        PrivacyScreenToggle = 0x279 => "privacyscreentoggle",
+        SelectiveScreenshot = 0x27a => "selectivescreenshot",
+        TriggerHappy1 = 0x2c0 => "trigger_happy1",
+        TriggerHappy2 = 0x2c1 => "trigger_happy2",
+        TriggerHappy3 = 0x2c2 => "trigger_happy3",
+        TriggerHappy4 = 0x2c3 => "trigger_happy4",
+        TriggerHappy5 = 0x2c4 => "trigger_happy5",
+        TriggerHappy6 = 0x2c5 => "trigger_happy6",
+        TriggerHappy7 = 0x2c6 => "trigger_happy7",
+        TriggerHappy8 = 0x2c7 => "trigger_happy8",
+        TriggerHappy9 = 0x2c8 => "trigger_happy9",
+        TriggerHappy10 = 0x2c9 => "trigger_happy10",
+        TriggerHappy11 = 0x2ca => "trigger_happy11",
+        TriggerHappy12 = 0x2cb => "trigger_happy12",
+        TriggerHappy13 = 0x2cc => "trigger_happy13",
+        TriggerHappy14 = 0x2cd => "trigger_happy14",
+        TriggerHappy15 = 0x2ce => "trigger_happy15",
+        TriggerHappy16 = 0x2cf => "trigger_happy16",
+        TriggerHappy17 = 0x2d0 => "trigger_happy17",
+        TriggerHappy18 = 0x2d1 => "trigger_happy18",
+        TriggerHappy19 = 0x2d2 => "trigger_happy19",
+        TriggerHappy20 = 0x2d3 => "trigger_happy20",
+        TriggerHappy21 = 0x2d4 => "trigger_happy21",
+        TriggerHappy22 = 0x2d5 => "trigger_happy22",
+        TriggerHappy23 = 0x2d6 => "trigger_happy23",
+        TriggerHappy24 = 0x2d7 => "trigger_happy24",
+        TriggerHappy25 = 0x2d8 => "trigger_happy25",
+        TriggerHappy26 = 0x2d9 => "trigger_happy26",
+        TriggerHappy27 = 0x2da => "trigger_happy27",
+        TriggerHappy28 = 0x2db => "trigger_happy28",
+        TriggerHappy29 = 0x2dc => "trigger_happy29",
+        TriggerHappy30 = 0x2dd => "trigger_happy30",
+        TriggerHappy31 = 0x2de => "trigger_happy31",
+        TriggerHappy32 = 0x2df => "trigger_happy32",
+        TriggerHappy33 = 0x2e0 => "trigger_happy33",
+        TriggerHappy34 = 0x2e1 => "trigger_happy34",
+        TriggerHappy35 = 0x2e2 => "trigger_happy35",
+        TriggerHappy36 = 0x2e3 => "trigger_happy36",
+        TriggerHappy37 = 0x2e4 => "trigger_happy37",
+        TriggerHappy38 = 0x2e5 => "trigger_happy38",
+        TriggerHappy39 = 0x2e6 => "trigger_happy39",
+        TriggerHappy40 = 0x2e7 => "trigger_happy40",
+    }
+    switches {
+        SwLid = 0 => "sw_lid",
+        SwTabletMode = 1 => "sw_tablet_mode",
+        SwHeadphoneInsert = 2 => "sw_headphone_insert",
+        SwRfkillAll = 3 => "sw_rfkill_all",
+        SwMicrophoneInsert = 4 => "sw_microphone_insert",
+        SwDock = 5 => "sw_dock",
+        SwLineoutInsert = 6 => "sw_lineout_insert",
+        SwJackPhysicalInsert = 7 => "sw_jack_physical_insert",
+        SwVideooutInsert = 8 => "sw_videoout_insert",
+        SwCameraLensCover = 9 => "sw_camera_lens_cover",
+        SwKeypadSlide = 10 => "sw_keypad_slide",
+        SwFrontProximity = 11 => "sw_front_proximity",
+        SwRotateLock = 12 => "sw_rotate_lock",
+        SwLineinInsert = 13 => "sw_linein_insert",
+        SwMuteDevice = 14 => "sw_mute_device",
+        SwPenInserted = 15 => "sw_pen_inserted",
+        SwMachineCover = 16 => "sw_machine_cover",
+    }
+    wheels {
+        WheelUp => "wheel_up",
+        WheelDown => "wheel_down",
+        HWheelLeft => "hwheel_left",
+        HWheelRight => "hwheel_right",
+    }
+    hats {
+        HatUp => "hat_up",
+        HatDown => "hat_down",
+        HatLeft => "hat_left",
+        HatRight => "hat_right",
+    }
+}
+
+impl Key {
+    /// The name [`get_key_for_name`] accepts for this key.
+    pub fn name(&self) -> String {
+        self.to_string()
+    }
+
+    /// Whether this is one of the keys held to change what others do, on either side.
+    pub fn is_modifier(&self) -> bool {
+        matches!(
+            self,
+            Key::LeftCtrl
+                | Key::RightCtrl
+                | Key::LeftShift
+                | Key::RightShift
+                | Key::LeftAlt
+                | Key::RightAlt
+                | Key::LeftMeta
+                | Key::RightMeta
+        )
+    }
+
+    /// Whether this follows a state of the device rather than a finger, like a switch or an axis
+    /// past a threshold, so that it may rightly stay held for hours.
+    pub fn is_latched(&self) -> bool {
+        matches!(self, Key::Threshold(_))
+            || Key::HATS.contains(self)
+            || Key::SWITCHES.iter().any(|(switch, _)| switch == self)
+    }
 }
 
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Key::Threshold(threshold) => write!(f, "{threshold}"),
+            Key::Raw(code) => write!(f, "{RAW_PREFIX}{code}"),
+            key => f.write_str(key.names()[0]),
+        }
+    }
+}
+
+/// What raw keycodes are written with, which is why no backend input can be named `code`.
+pub const RAW_PREFIX: &str = "code:";
+
 static KEY_NAMES: OnceLock<HashMap<&'static str, Key>> = OnceLock::new();
 
-static KEY_MULTI_OVERRIDE: OnceLock<HashMap<&'static str, Vec<Key>>> = OnceLock::new();
+/// Built-in groups, matching any of their keys.
+pub const GROUPS: &[(&str, &[Key])] = &[
+    ("ctrl", &[Key::LeftCtrl, Key::RightCtrl]),
+    ("alt", &[Key::LeftAlt, Key::RightAlt]),
+    ("shift", &[Key::LeftShift, Key::RightShift]),
+    ("meta", &[Key::LeftMeta, Key::RightMeta]),
+    (
+        "digit",
+        &[
+            Key::N0,
+            Key::N1,
+            Key::N2,
+            Key::N3,
+            Key::N4,
+            Key::N5,
+            Key::N6,
+            Key::N7,
+            Key::N8,
+            Key::N9,
+        ],
+    ),
+    (
+        "letter",
+        &[
+            Key::A,
+            Key::B,
+            Key::C,
+            Key::D,
+            Key::E,
+            Key::F,
+            Key::G,
+            Key::H,
+            Key::I,
+            Key::J,
+            Key::K,
+            Key::L,
+            Key::M,
+            Key::N,
+            Key::O,
+            Key::P,
+            Key::Q,
+            Key::R,
+            Key::S,
+            Key::T,
+            Key::U,
+            Key::V,
+            Key::W,
+            Key::X,
+            Key::Y,
+            Key::Z,
+        ],
+    ),
+    (
+        "fn",
+        &[
+            Key::F1,
+            Key::F2,
+            Key::F3,
+            Key::F4,
+            Key::F5,
+            Key::F6,
+            Key::F7,
+            Key::F8,
+            Key::F9,
+            Key::F10,
+            Key::F11,
+            Key::F12,
+            Key::F13,
+            Key::F14,
+            Key::F15,
+            Key::F16,
+            Key::F17,
+            Key::F18,
+            Key::F19,
+            Key::F20,
+            Key::F21,
+            Key::F22,
+            Key::F23,
+            Key::F24,
+        ],
+    ),
+    (
+        "keypad",
+        &[
+            Key::KP0,
+            Key::KP1,
+            Key::KP2,
+            Key::KP3,
+            Key::KP4,
+            Key::KP5,
+            Key::KP6,
+            Key::KP7,
+            Key::KP8,
+            Key::KP9,
+            Key::KPDot,
+            Key::KPComma,
+            Key::KPJPComma,
+            Key::KPPlus,
+            Key::KPMinus,
+            Key::KPAsterisk,
+            Key::KPSlash,
+            Key::KPEqual,
+            Key::KPPlusMinus,
+            Key::KPLeftParen,
+            Key::KPRightParen,
+            Key::KPEnter,
+        ],
+    ),
+];
 
-pub fn key_override(name: &str) -> Option<Vec<Key>> {
-    KEY_MULTI_OVERRIDE
-        .get_or_init(|| {
-            let mut map = HashMap::new();
-            map.insert("ctrl", vec![Key::LeftCtrl, Key::RightCtrl]);
-            map.insert("alt", vec![Key::LeftAlt, Key::RightAlt]);
-            map.insert("shift", vec![Key::LeftShift, Key::RightShift]);
-            map.insert("meta", vec![Key::LeftMeta, Key::RightMeta]);
-            map
-        })
-        .get(name)
-        .cloned()
+/// The keys of the built-in group with the given name.
+pub fn key_override(name: &str) -> Option<&'static [Key]> {
+    let group = GROUPS.iter().find(|(group, _)| *group == name);
+    group.map(|(_, keys)| *keys)
 }
 
 pub fn get_key_for_name(name: &str) -> Option<Key> {
     KEY_NAMES
         .get_or_init(|| {
-            let mut map = HashMap::new();
-            map.insert("esc", Key::Esc);
-            map.insert("f1", Key::F1);
-            map.insert("f2", Key::F2);
-            map.insert("f3", Key::F3);
-            map.insert("f4", Key::F4);
-            map.insert("f5", Key::F5);
-            map.insert("f6", Key::F6);
-            map.insert("f7", Key::F7);
-            map.insert("f8", Key::F8);
-            map.insert("f9", Key::F9);
-            map.insert("f10", Key::F10);
-            map.insert("f11", Key::F11);
-            map.insert("f12", Key::F12);
-            map.insert("f13", Key::F13);
-            map.insert("f14", Key::F14);
-            map.insert("f15", Key::F15);
-            map.insert("f16", Key::F16);
-            map.insert("f17", Key::F17);
-            map.insert("f18", Key::F18);
-            map.insert("f19", Key::F19);
-            map.insert("f20", Key::F20);
-            map.insert("f21", Key::F21);
-            map.insert("f22", Key::F22);
-            map.insert("f23", Key::F23);
-            map.insert("f24", Key::F24);
-            map.insert("leftctrl", Key::LeftCtrl);
-            map.insert("rightctrl", Key::RightCtrl);
-            map.insert("leftalt", Key::LeftAlt);
-            map.insert("rightalt", Key::RightAlt);
-            map.insert("leftshift", Key::LeftShift);
-            map.insert("rightshift", Key::RightShift);
-            map.insert("leftmeta", Key::LeftMeta);
-            map.insert("rightmeta", Key::RightMeta);
-            map.insert("n1", Key::N1);
-            map.insert("n2", Key::N2);
-            map.insert("n3", Key::N3);
-            map.insert("n4", Key::N4);
-            map.insert("n5", Key::N5);
-            map.insert("n6", Key::N6);
-            map.insert("n7", Key::N7);
-            map.insert("n8", Key::N8);
-            map.insert("n9", Key::N9);
-            map.insert("n0", Key::N0);
-            map.insert("1", Key::N1);
-            map.insert("2", Key::N2);
-            map.insert("3", Key::N3);
-            map.insert("4", Key::N4);
-            map.insert("5", Key::N5);
-            map.insert("6", Key::N6);
-            map.insert("7", Key::N7);
-            map.insert("8", Key::N8);
-            map.insert("9", Key::N9);
-            map.insert("0", Key::N0);
-            map.insert("q", Key::Q);
-            map.insert("w", Key::W);
-            map.insert("e", Key::E);
-            map.insert("r", Key::R);
-            map.insert("t", Key::T);
-            map.insert("y", Key::Y);
-            map.insert("u", Key::U);
-            map.insert("i", Key::I);
-            map.insert("o", Key::O);
-            map.insert("p", Key::P);
-            map.insert("a", Key::A);
-            map.insert("s", Key::S);
-            map.insert("d", Key::D);
-            map.insert("f", Key::F);
-            map.insert("g", Key::G);
-            map.insert("h", Key::H);
-            map.insert("j", Key::J);
-            map.insert("k", Key::K);
-            map.insert("l", Key::L);
-            map.insert("z", Key::Z);
-            map.insert("x", Key::X);
-            map.insert("c", Key::C);
-            map.insert("v", Key::V);
-            map.insert("b", Key::B);
-            map.insert("n", Key::N);
-            map.insert("m", Key::M);
-            map.insert("minus", Key::Minus);
-            map.insert("dash", Key::Minus);
-            map.insert("equal", Key::Equal);
-            map.insert("plus", Key::Equal);
-            map.insert("backspace", Key::Backspace);
-            map.insert("tab", Key::Tab);
-            map.insert("leftbracket", Key::LeftBracket);
-            map.insert("leftbrace", Key::LeftBracket);
-            map.insert("rightbracket", Key::RightBracket);
-            map.insert("rightbrace", Key::RightBracket);
-            map.insert("enter", Key::Enter);
-            map.insert("semicolon", Key::Semicolon);
-            map.insert("apostrophe", Key::Apostrophe);
-            map.insert("grave", Key::Grave);
-            map.insert("tilde", Key::Grave);
-            map.insert("backslash", Key::Backslash);
-            map.insert("comma", Key::Comma);
-            map.insert("dot", Key::Dot);
-            map.insert("slash", Key::Slash);
-            map.insert("space", Key::Space);
-            map.insert("capslock", Key::CapsLock);
-            map.insert("numlock", Key::NumLock);
-            map.insert("scrolllock", Key::ScrollLock);
-            map.insert("kp0", Key::KP0);
-            map.insert("kp1", Key::KP1);
-            map.insert("kp2", Key::KP2);
-            map.insert("kp3", Key::KP3);
-            map.insert("kp4", Key::KP4);
-            map.insert("kp5", Key::KP5);
-            map.insert("kp6", Key::KP6);
-            map.insert("kp7", Key::KP7);
-            map.insert("kp8", Key::KP8);
-            map.insert("kp9", Key::KP9);
-            map.insert("np0", Key::KP0);
-            map.insert("np1", Key::KP1);
-            map.insert("np2", Key::KP2);
-            map.insert("np3", Key::KP3);
-            map.insert("np4", Key::KP4);
-            map.insert("np5", Key::KP5);
-            map.insert("np6", Key::KP6);
-            map.insert("np7", Key::KP7);
-            map.insert("np8", Key::KP8);
-            map.insert("np9", Key::KP9);
-            map.insert("numpad0", Key::KP0);
-            map.insert("numpad1", Key::KP1);
-            map.insert("numpad2", Key::KP2);
-            map.insert("numpad3", Key::KP3);
-            map.insert("numpad4", Key::KP4);
-            map.insert("numpad5", Key::KP5);
-            map.insert("numpad6", Key::KP6);
-            map.insert("numpad7", Key::KP7);
-            map.insert("numpad8", Key::KP8);
-            map.insert("numpad9", Key::KP9);
-            map.insert("kpenter", Key::KPEnter);
-            map.insert("npenter", Key::KPEnter);
-            map.insert("numpadenter", Key::KPEnter);
-            map.insert("kpdot", Key::KPDot);
-            map.insert("kpminus", Key::KPMinus);
-            map.insert("kpplus", Key::KPPlus);
-            map.insert("kpasterisk", Key::KPAsterisk);
-            map.insert("kpslash", Key::KPSlash);
-            map.insert("kpjpcomma", Key::KPJPComma);
-            map.insert("npdot", Key::KPDot);
-            map.insert("npminus", Key::KPMinus);
-            map.insert("npplus", Key::KPPlus);
-            map.insert("npasterisk", Key::KPAsterisk);
-            map.insert("npslash", Key::KPSlash);
-            map.insert("npjpcomma", Key::KPJPComma);
-            map.insert("numpaddot", Key::KPDot);
-            map.insert("numpadminus", Key::KPMinus);
-            map.insert("numpadplus", Key::KPPlus);
-            map.insert("numpadasterisk", Key::KPAsterisk);
-            map.insert("numpadslash", Key::KPSlash);
-            map.insert("numpadjpcomma", Key::KPJPComma);
-            map.insert("zenkakuhankaku", Key::Zenkakuhankaku);
-            map.insert("ro", Key::Ro);
-            map.insert("katakana", Key::Katakana);
-            map.insert("hiragana", Key::Hiragana);
-            map.insert("henkan", Key::Henkan);
-            map.insert("katakanahiragana", Key::Katakanahiragana);
-            map.insert("muhenkan", Key::Muhenkan);
-            map.insert("sysrq", Key::SysRq);
-            map.insert("linefeed", Key::Linefeed);
-            map.insert("home", Key::Home);
-            map.insert("up", Key::Up);
-            map.insert("uparrow", Key::Up);
-            map.insert("pageup", Key::Pageup);
-            map.insert("left", Key::Left);
-            map.insert("leftarrow", Key::Left);
-            map.insert("right", Key::Right);
-            map.insert("rightarrow", Key::Right);
-            map.insert("end", Key::End);
-            map.insert("down", Key::Down);
-            map.insert("downarrow", Key::Down);
-            map.insert("pagedown", Key::Pagedown);
-            map.insert("insert", Key::Insert);
-            map.insert("delete", Key::Delete);
-            map.insert("macro", Key::Macro);
-            map.insert("mute", Key::Mute);
-            map.insert("volumedown", Key::VolumeDown);
-            map.insert("volumeup", Key::VolumeUp);
-            map.insert("power", Key::Power);
-            map.insert("kpequal", Key::KPEqual);
-            map.insert("kpplusminus", Key::KPPlusMinus);
-            map.insert("pause", Key::Pause);
-            map.insert("scale", Key::Scale);
-            map.insert("kpcomma", Key::KPComma);
-            map.insert("hangeul", Key::Hangeul);
-            map.insert("hanja", Key::Hanja);
-            map.insert("yen", Key::Yen);
-            map.insert("compose", Key::Compose);
-            map.insert("again", Key::Again);
-            map.insert("props", Key::Props);
-            map.insert("undo", Key::Undo);
-            map.insert("front", Key::Front);
-            map.insert("copy", Key::Copy);
-            map.insert("open", Key::Open);
-            map.insert("paste", Key::Paste);
-            map.insert("find", Key::Find);
-            map.insert("cut", Key::Cut);
-            map.insert("help", Key::Help);
-            map.insert("menu", Key::Menu);
-            map.insert("calc", Key::Calc);
-            map.insert("calculator", Key::Calc);
-            map.insert("setup", Key::Setup);
-            map.insert("sleep", Key::Sleep);
-            map.insert("wakeup", Key::Wakeup);
-            map.insert("file", Key::File);
-            map.insert("sendfile", Key::SendFile);
-            map.insert("deletefile", Key::DeleteFile);
-            map.insert("xfer", Key::Xfer);
-            map.insert("prog1", Key::Prog1);
-            map.insert("prog2", Key::Prog2);
-            map.insert("www", Key::WWW);
-            map.insert("msdos", Key::MSDOS);
-            map.insert("screenlock", Key::ScreenLock);
-            map.insert("rotatedisplay", Key::RotateDisplay);
-            map.insert("cyclewindows", Key::CycleWindows);
-            map.insert("mail", Key::Mail);
-            map.insert("bookmarks", Key::Bookmarks);
-            map.insert("computer", Key::Computer);
-            map.insert("back", Key::Back);
-            map.insert("forward", Key::Forward);
-            map.insert("closecd", Key::CloseCD);
-            map.insert("ejectcd", Key::EjectCD);
-            map.insert("ejectclosecd", Key::EjectCloseCD);
-            map.insert("nextsong", Key::NextSong);
-            map.insert("playpause", Key::PlayPause);
-            map.insert("previoussong", Key::PreviousSong);
-            map.insert("stopcd", Key::StopCD);
-            map.insert("record", Key::Record);
-            map.insert("rewind", Key::Rewind);
-            map.insert("phone", Key::Phone);
-            map.insert("iso", Key::Iso);
-            map.insert("config", Key::Config);
-            map.insert("homepage", Key::Homepage);
-            map.insert("refresh", Key::Refresh);
-            map.insert("exit", Key::Exit);
-            map.insert("move", Key::Move);
-            map.insert("edit", Key::Edit);
-            map.insert("scrollup", Key::ScrollUp);
-            map.insert("scrolldown", Key::ScrollDown);
-            map.insert("kpleftparen", Key::KPLeftParen);
-            map.insert("kprightparen", Key::KPRightParen);
-            map.insert("new", Key::New);
-            map.insert("redo", Key::Redo);
-            map.insert("playcd", Key::PlayCD);
-            map.insert("pausecd", Key::PauseCD);
-            map.insert("prog3", Key::Prog3);
-            map.insert("prog4", Key::Prog4);
-            map.insert("allapplications", Key::AllApplications);
-            map.insert("suspend", Key::Suspend);
-            map.insert("close", Key::Close);
-            map.insert("play", Key::Play);
-            map.insert("fastforward", Key::FastForward);
-            map.insert("bassboost", Key::BassBoost);
-            map.insert("print", Key::Print);
-            map.insert("hp", Key::Hp);
-            map.insert("camera", Key::Camera);
-            map.insert("sound", Key::Sound);
-            map.insert("question", Key::Question);
-            map.insert("email", Key::Email);
-            map.insert("chat", Key::Chat);
-            map.insert("search", Key::Search);
-            map.insert("connect", Key::Connect);
-            map.insert("finance", Key::Finance);
-            map.insert("sport", Key::Sport);
-            map.insert("shop", Key::Shop);
-            map.insert("alterase", Key::AltErase);
-            map.insert("cancel", Key::Cancel);
-            map.insert("brightnessdown", Key::BrightnessDown);
-            map.insert("brightnessup", Key::BrightnessUp);
-            map.insert("media", Key::Media);
-            map.insert("switchvideomode", Key::SwitchVideoMode);
-            map.insert("send", Key::Send);
-            map.insert("reply", Key::Reply);
-            map.insert("forwardmail", Key::ForwardMail);
-            map.insert("save", Key::Save);
-            map.insert("documents", Key::Documents);
-            map.insert("battery", Key::Battery);
-            map.insert("bluetooth", Key::Bluetooth);
-            map.insert("wlan", Key::WLAN);
-            map.insert("uwb", Key::UWB);
-            map.insert("videonext", Key::VideoNext);
-            map.insert("videoprev", Key::VideoPrev);
-            map.insert("brightnesscycle", Key::BrightnessCycle);
-            map.insert("brightnessauto", Key::BrightnessAuto);
-            map.insert("displayoff", Key::DisplayOff);
-            map.insert("wwan", Key::WWAN);
-            map.insert("rfkill", Key::RFKill);
-            map.insert("micmute", Key::MicMute);
-            map
+            let codes = Key::KEYS.iter().chain(Key::SWITCHES);
+            let keys = codes
+                .map(|(key, _)| key)
+                .chain(Key::WHEELS)
+                .chain(Key::HATS);
+            keys.flat_map(|key| key.names().iter().map(move |name| (*name, *key)))
+                .collect()
         })
         .get(name)
         .cloned()
+        .or_else(|| raw_key(name))
+        .or_else(|| Some(Key::Threshold(name.parse().ok()?)))
+}
+
+/// Parses `code:NNN` and `0xNN`, as the named key if the code has one.
+fn raw_key(name: &str) -> Option<Key> {
+    let code = match (name.strip_prefix(RAW_PREFIX), name.strip_prefix("0x")) {
+        (Some(code), _) => code.parse().ok()?,
+        (_, Some(code)) => u16::from_str_radix(code, 16).ok()?,
+        _ => return None,
+    };
+    Some(Key::from_code(code))
+}
+
+/// An absolute axis of a gamepad or joystick.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display)]
+pub enum Axis {
+    #[display("left_x")]
+    LeftX,
+    #[display("left_y")]
+    LeftY,
+    #[display("left_trigger")]
+    LeftTrigger,
+    #[display("right_x")]
+    RightX,
+    #[display("right_y")]
+    RightY,
+    #[display("right_trigger")]
+    RightTrigger,
+    #[display("throttle")]
+    Throttle,
+    #[display("rudder")]
+    Rudder,
+    #[display("gas")]
+    Gas,
+    #[display("brake")]
+    Brake,
+    #[display("hat_x")]
+    HatX,
+    #[display("hat_y")]
+    HatY,
+}
+
+impl Axis {
+    const ALL: [Axis; 12] = [
+        Axis::LeftX,
+        Axis::LeftY,
+        Axis::LeftTrigger,
+        Axis::RightX,
+        Axis::RightY,
+        Axis::RightTrigger,
+        Axis::Throttle,
+        Axis::Rudder,
+        Axis::Gas,
+        Axis::Brake,
+        Axis::HatX,
+        Axis::HatY,
+    ];
+
+    /// Whether the axis rests in the middle of its range, like sticks and hats, rather than at
+    /// its minimum, like triggers and pedals.
+    pub fn is_centered(self) -> bool {
+        match self {
+            Axis::LeftX | Axis::LeftY | Axis::RightX | Axis::RightY => true,
+            Axis::Rudder | Axis::HatX | Axis::HatY => true,
+            Axis::LeftTrigger | Axis::RightTrigger => false,
+            Axis::Throttle | Axis::Gas | Axis::Brake => false,
+        }
+    }
+}
+
+/// Held while an axis is above or below a position, given in percent of its range: from -100 to
+/// 100 for [centered](Axis::is_centered) axes like sticks, from 0 to 100 otherwise.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display)]
+#[display("{axis}{}{percent}%", if *above { ">" } else { "<" })]
+pub struct Threshold {
+    pub axis: Axis,
+    pub above: bool,
+    pub percent: i32,
+}
+
+impl Threshold {
+    /// Whether the axis at `percent` holds the key. Once held, the axis has to move back
+    /// `hysteresis` percent past the threshold to release it, so that jitter does not.
+    pub fn holds(&self, percent: i32, held: bool, hysteresis: i32) -> bool {
+        let margin = if held { hysteresis } else { 0 };
+        if self.above {
+            percent > self.percent - margin
+        } else {
+            percent < self.percent + margin
+        }
+    }
+
+    /// The direction keys a hat axis presses, with the threshold for each. They are apart from
+    /// the `dpad_*` buttons, which some gamepads report alongside the hat.
+    pub fn hat(axis: Axis) -> Vec<(Key, Threshold)> {
+        let (negative, positive) = match axis {
+            Axis::HatX => (Key::HatLeft, Key::HatRight),
+            Axis::HatY => (Key::HatUp, Key::HatDown),
+            _ => return Vec::new(),
+        };
+        let threshold = |above, percent| Threshold {
+            axis,
+            above,
+            percent,
+        };
+        vec![
+            (negative, threshold(false, -50)),
+            (positive, threshold(true, 50)),
+        ]
+    }
+}
+
+impl FromStr for Threshold {
+    type Err = ();
+
+    /// Parses `left_trigger>50%` and the like.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (at, above) = match (s.find('>'), s.find('<')) {
+            (Some(at), None) => (at, true),
+            (None, Some(at)) => (at, false),
+            _ => return Err(()),
+        };
+        let axis = Axis::ALL
+            .into_iter()
+            .find(|axis| axis.to_string() == s[..at])
+            .ok_or(())?;
+        let percent = s[at + 1..]
+            .strip_suffix('%')
+            .and_then(|percent| percent.parse().ok())
+            .filter(|percent: &i32| (-100..=100).contains(percent))
+            .ok_or(())?;
+        Ok(Self {
+            axis,
+            above,
+            percent,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::key::{self, Axis, Key, Threshold};
+    use std::collections::HashSet;
+
+    /// The `RecognizedKeys` file, as the key table describes it.
+    fn recognized_keys() -> String {
+        let mut doc = String::from(
+            "# Names accepted in chord sequences, generated from the key table in src/key.rs.\n\
+             # Each line gives the name a key is printed with, its aliases, then its evdev code.\n\
+             # Other keys can be written by code as code:NNN or 0xNN, like monitor prints them.\n",
+        );
+        let line = |key: &Key, code: Option<u16>| {
+            let names = key.names().join(" ");
+            match code {
+                Some(code) => format!("{names:<32}{code}\n"),
+                None => format!("{names}\n"),
+            }
+        };
+        doc += "\n# Keys and buttons (EV_KEY)\n";
+        for (key, code) in Key::KEYS {
+            doc += &line(key, Some(*code));
+        }
+        doc += "\n# Switches, held while on (EV_SW)\n";
+        for (key, code) in Key::SWITCHES {
+            doc += &line(key, Some(*code));
+        }
+        doc += "\n# Wheel movements, pressed and released at once\n";
+        for key in Key::WHEELS {
+            doc += &line(key, None);
+        }
+        doc += "\n# Hat directions, held while the hat points that way\n";
+        for key in Key::HATS {
+            doc += &line(key, None);
+        }
+        doc += "\n# Groups, matching any of their keys\n";
+        for (group, keys) in key::GROUPS {
+            let names = keys.iter().map(Key::name).collect::<Vec<_>>();
+            doc += &format!("{group:<32}{}\n", names.join(" "));
+        }
+        doc += "\n# Axes, held beyond a position in percent like left_trigger>50% or left_x<-50%\n";
+        for axis in Axis::ALL {
+            doc += &format!("{axis}\n");
+        }
+        doc
+    }
+
+    /// Set `UPDATE_RECOGNIZED_KEYS=1` to rewrite the file after changing the table.
+    #[test]
+    fn recognized_keys_match_the_table() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/RecognizedKeys");
+        let doc = recognized_keys();
+        if std::env::var_os("UPDATE_RECOGNIZED_KEYS").is_some() {
+            std::fs::write(path, &doc).expect("Writing");
+        }
+        let written = std::fs::read_to_string(path).expect("Reading");
+        assert!(written == doc, "RecognizedKeys is out of date");
+    }
+
+    #[test]
+    fn names_round_trip() {
+        let mut names = HashSet::new();
+        let codes = Key::KEYS.iter().chain(Key::SWITCHES);
+        let keys = codes
+            .map(|(key, _)| *key)
+            .chain(Key::WHEELS.iter().chain(Key::HATS).copied());
+        let special = [
+            Key::Raw(456),
+            Key::Threshold(Threshold {
+                axis: Axis::LeftTrigger,
+                above: true,
+                percent: 50,
+            }),
+            Key::Threshold(Threshold {
+                axis: Axis::LeftX,
+                above: false,
+                percent: -50,
+            }),
+        ];
+        for (group, _) in key::GROUPS {
+            assert!(names.insert(group), "{group} names a key and a group");
+        }
+        for key in keys.chain(special) {
+            assert_eq!(key::get_key_for_name(&key.to_string()), Some(key));
+            for name in key.names() {
+                assert!(names.insert(name), "{name} names two keys");
+                assert_eq!(key::get_key_for_name(name), Some(key));
+            }
+        }
+    }
+
+    #[test]
+    fn codes_round_trip() {
+        for (key, code) in Key::KEYS {
+            assert_eq!(Key::from_code(*code), *key);
+        }
+        for (key, code) in Key::SWITCHES {
+            assert_eq!(Key::from_switch(*code), Some(*key));
+        }
+        assert_eq!(Key::from_code(0x1c8), Key::Raw(0x1c8));
+        assert_eq!(key::get_key_for_name("0x1d0"), Some(Key::Fn));
+        assert_eq!(key::get_key_for_name("coffee"), Some(Key::ScreenLock));
+    }
 }
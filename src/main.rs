@@ -2,7 +2,18 @@ use anyhow::Context;
 use clap::Parser;
 use log::LevelFilter;
 
-use crate::cli::Cli;
+use crossbeam_channel::{select, Receiver};
+use signal_hook::{
+    consts::{SIGINT, SIGTERM},
+    iterator::Signals,
+};
+use std::thread;
+
+use crate::{
+    cli::{Cli, Command},
+    control::server::AlreadyRunning,
+    supervisor::Supervisor,
+};
 
 const QUALIFIER: &str = "dev";
 const ORGANIZATION: &str = "luminasapphira";
@@ -12,8 +23,15 @@ mod backend;
 mod chord;
 mod cli;
 mod config;
+mod control;
+#[cfg(feature = "backend-evdev")]
+mod devices;
 mod exec;
 mod key;
+mod monitor;
+mod reload;
+mod service;
+mod supervisor;
 
 fn main() -> anyhow::Result<()> {
     pretty_env_logger::formatted_timed_builder()
@@ -21,27 +39,80 @@ fn main() -> anyhow::Result<()> {
         .parse_env("SYSTEMCHORD_LOG")
         .init();
     let cli = Cli::parse();
+    match cli.command {
+        Some(Command::Ctl { command }) => return control::client::run(command),
+        Some(Command::Monitor { device, executor }) => {
+            return monitor::run(cli.config, device, executor)
+        }
+        #[cfg(feature = "backend-evdev")]
+        Some(Command::ListDevices { json }) => return devices::list(json),
+        None => {}
+    }
     log::info!("Starting {APPLICATION}");
 
+    let (config_path, _) = config::config_path(cli.config.clone());
     let config = config::load_config(cli.config).context("Loading config")?;
+    let mut reloads = reload::watch(&config_path).context("Watching for config reloads")?;
+    let stops = stop_signals().context("Installing stop signal handlers")?;
+    // removes the socket once dropped, after the services stopped
+    let server = match control::server::listen() {
+        Ok(server) => Some(server),
+        // both would fight over the same devices
+        Err(err) if err.is::<AlreadyRunning>() => return Err(err),
+        Err(err) => {
+            log::error!("The control socket is unavailable: {err:#}");
+            None
+        }
+    };
+    let requests = server
+        .as_ref()
+        .map_or_else(crossbeam_channel::never, |server| server.requests.clone());
 
-    let mut handles = Vec::with_capacity(config.executors.len() * 2);
-
-    for executor in config.executors {
-        log::info!("Starting chord service: {}", &executor.backend);
-        let (recv, handle) = backend::start_backend(executor.backend);
-        handles.push(handle);
-        handles.push(chord::chord_handler(
-            recv,
-            executor.chords,
-            executor.chord_options,
-            executor.shell,
-        ));
-    }
+    let mut supervisor = Supervisor::new(config_path);
+    let finished = supervisor.finished();
+    supervisor.apply_config(config);
 
-    for handle in handles {
-        handle.join().expect("Thread panicked");
+    // a reload can start services again, so only without reloads does running out of them end it
+    let mut watching = true;
+    while watching || supervisor.is_running() {
+        select! {
+            recv(reloads) -> reason => match reason {
+                Ok(reason) => {
+                    log::info!("Reloading config: {reason}");
+                    let _ = supervisor.reload();
+                }
+                Err(_) => {
+                    log::warn!("No longer watching for reloads, exiting once no service is left");
+                    reloads = crossbeam_channel::never();
+                    watching = false;
+                }
+            },
+            recv(requests) -> request => {
+                if let Ok((request, reply)) = request {
+                    let _ = reply.send(supervisor.handle(request));
+                }
+            }
+            recv(finished) -> _ => supervisor.reap(),
+            recv(stops) -> _ => {
+                log::info!("Stopping {APPLICATION}");
+                break;
+            }
+        }
     }
 
+    supervisor.stop();
+
     Ok(())
 }
+
+/// Tells the receiver once SIGINT or SIGTERM arrives, so that the services can stop cleanly.
+fn stop_signals() -> anyhow::Result<Receiver<()>> {
+    let (sender, recv) = crossbeam_channel::bounded(1);
+    let mut signals = Signals::new([SIGINT, SIGTERM])?;
+    thread::spawn(move || {
+        for _ in signals.forever() {
+            let _ = sender.try_send(());
+        }
+    });
+    Ok(recv)
+}
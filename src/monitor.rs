@@ -0,0 +1,103 @@
+#[cfg(feature = "backend-evdev")]
+use crate::config::DeviceMatcher;
+use crate::{
+    backend::{self, Event, Source},
+    config::{self, Backend},
+    control::Selector,
+    key::Key,
+};
+use anyhow::{anyhow, Context};
+use std::{collections::BTreeSet, path::PathBuf};
+
+/// Prints the keys of a backend as they change, to help writing chord sequences.
+///
+/// Uses `device` when given, otherwise the backend of the selected executor in the config.
+pub fn run(
+    config_path: Option<PathBuf>,
+    device: Option<PathBuf>,
+    executor: Option<String>,
+) -> anyhow::Result<()> {
+    let backend = match device {
+        #[cfg(feature = "backend-evdev")]
+        Some(device) => Backend::Evdev {
+            devices: DeviceMatcher {
+                device: Some(device),
+                ..Default::default()
+            },
+            inputs: Default::default(),
+            retry: false,
+        },
+        #[cfg(not(feature = "backend-evdev"))]
+        Some(_) => return Err(anyhow!("Device paths need the evdev backend")),
+        None => {
+            let config = config::load_config(config_path).context("Loading config")?;
+            let selector = Selector::from(executor.unwrap_or_else(|| "1".to_string()));
+            let executor = config
+                .executors
+                .into_iter()
+                .enumerate()
+                .find(|(index, executor)| selector.selects(*index, executor.name.as_deref()))
+                .ok_or(anyhow!("No executor {selector} in the config"))?;
+            executor.1.backend
+        }
+    };
+
+    println!("Monitoring {backend}, press Ctrl+C to stop");
+    let started = backend::start_backend(backend, None).context("Starting backend")?;
+    let mut held = BTreeSet::new();
+    for event in started.events {
+        match event {
+            Event::Pressed(key, source, _) => {
+                let name = name(&(source.clone(), key));
+                held.insert((source, key));
+                println!("pressed  {name:<16} held: {}", names(&held));
+                println!("    sequence = [{}]", quoted(&held));
+            }
+            Event::Released(key, source, _) => {
+                let name = name(&(source.clone(), key));
+                held.remove(&(source, key));
+                println!("released {name:<16} held: {}", names(&held));
+            }
+            Event::Axis(axis, source, percent, _) => {
+                let axis = match source {
+                    Some(input) => format!("{input}:{axis}"),
+                    None => axis.to_string(),
+                };
+                println!("axis     {axis:<16} at {percent}%");
+            }
+            Event::Sync(keys) => {
+                held = keys
+                    .into_iter()
+                    .map(|(key, source)| (source, key))
+                    .collect();
+                println!("{:<25} held: {}", "synced", names(&held));
+            }
+            Event::Stop => {
+                held.clear();
+                println!("device disconnected");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The key as a chord would name it, along with its input if the backend has named ones.
+fn name((source, key): &(Source, Key)) -> String {
+    match source {
+        Some(input) => format!("{input}:{}", key.name()),
+        None => key.name(),
+    }
+}
+
+fn names(held: &BTreeSet<(Source, Key)>) -> String {
+    let names: Vec<_> = held.iter().map(name).collect();
+    names.join(" + ")
+}
+
+fn quoted(held: &BTreeSet<(Source, Key)>) -> String {
+    let names: Vec<_> = held
+        .iter()
+        .map(|held| format!("\"{}\"", name(held)))
+        .collect();
+    names.join(", ")
+}
@@ -0,0 +1,71 @@
+use anyhow::{anyhow, Context};
+use crossbeam_channel::{Receiver, Sender};
+use nix::sys::inotify::{AddWatchFlags, InitFlags, Inotify};
+use signal_hook::{consts::SIGHUP, iterator::Signals};
+use std::{path::Path, thread};
+
+/// Why the config should be reloaded.
+#[derive(Debug, derive_more::Display)]
+pub enum Reason {
+    #[display("received SIGHUP")]
+    Hangup,
+    #[display("config file changed")]
+    Changed,
+}
+
+/// Watches for SIGHUP and for changes to the config file, telling the receiver to reload. Only
+/// failing to handle SIGHUP is an error.
+pub fn watch(config_path: &Path) -> anyhow::Result<Receiver<Reason>> {
+    let (sender, recv) = crossbeam_channel::unbounded();
+
+    let mut signals = Signals::new([SIGHUP]).context("Installing SIGHUP handler")?;
+    let signal_sender = sender.clone();
+    thread::spawn(move || {
+        for _ in signals.forever() {
+            if signal_sender.send(Reason::Hangup).is_err() {
+                break;
+            }
+        }
+    });
+
+    // SIGHUP still reloads without it
+    if let Err(err) = watch_file(config_path, sender) {
+        log::warn!("Cannot watch the config file, reload it with SIGHUP instead: {err:#}");
+    }
+    Ok(recv)
+}
+
+/// Watches the directory of the file rather than the file itself, since editors tend to replace
+/// files instead of writing to them.
+fn watch_file(path: &Path, sender: Sender<Reason>) -> anyhow::Result<()> {
+    let file_name = path
+        .file_name()
+        .ok_or(anyhow!("Config path has no file name"))?
+        .to_owned();
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let inotify = Inotify::init(InitFlags::IN_CLOEXEC)?;
+    inotify.add_watch(
+        dir,
+        AddWatchFlags::IN_CLOSE_WRITE | AddWatchFlags::IN_MOVED_TO,
+    )?;
+    thread::spawn(move || loop {
+        let events = match inotify.read_events() {
+            Ok(events) => events,
+            Err(err) => {
+                log::error!("Stopped watching the config file: {err}");
+                break;
+            }
+        };
+        if events
+            .iter()
+            .any(|event| event.name.as_ref() == Some(&file_name))
+            && sender.send(Reason::Changed).is_err()
+        {
+            break;
+        }
+    });
+    Ok(())
+}
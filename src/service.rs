@@ -0,0 +1,177 @@
+use crate::{
+    backend::{self, Stats, Stopper},
+    chord::{self, Bindings, Control, Status},
+    config::{Backend, Executor},
+    control::Selector,
+};
+use crossbeam_channel::Sender;
+use std::{
+    io,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    thread::JoinHandle,
+    time::Duration,
+};
+
+/// How long to wait for a chord handler to answer a control request.
+const CONTROL_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// A running executor: its backend and the chord handler listening to it.
+pub struct Service {
+    /// Position of the executor in the config.
+    index: usize,
+    name: Option<String>,
+    backend: Backend,
+    grab: bool,
+    control: Sender<Control>,
+    /// Waits for the backend and the chord handler, then sets `ended`.
+    watcher: JoinHandle<()>,
+    ended: Arc<AtomicBool>,
+    stopper: Stopper,
+    stats: Arc<Stats>,
+}
+
+impl Service {
+    /// Starts the executor, sending on `finished` once it stopped, whatever the reason.
+    pub fn start(index: usize, executor: Executor, finished: Sender<()>) -> io::Result<Self> {
+        log::info!("Starting chord service: {}", &executor.backend);
+        let (name, backend, grab, bindings) = split(executor);
+        let (verdict_sender, verdict_recv) = if grab {
+            let (sender, recv) = crossbeam_channel::bounded(1);
+            (Some(sender), Some(recv))
+        } else {
+            (None, None)
+        };
+        let started = backend::start_backend(backend.clone(), verdict_recv)?;
+        let (control, control_recv) = crossbeam_channel::unbounded();
+        let resync = started.stopper.resync()?;
+        let handler_handle = chord::chord_handler(
+            started.events,
+            bindings,
+            verdict_sender,
+            control_recv,
+            resync,
+        );
+        let ended = Arc::new(AtomicBool::new(false));
+        let watcher = {
+            let ended = ended.clone();
+            thread::spawn(move || {
+                // join both, even after one of them panicked
+                let joined = [started.handle, handler_handle].map(JoinHandle::join);
+                let panicked = joined.iter().any(Result::is_err);
+                ended.store(true, Ordering::Release);
+                let _ = finished.send(());
+                if panicked {
+                    panic!("Thread panicked");
+                }
+            })
+        };
+        Ok(Self {
+            index,
+            name,
+            backend,
+            grab,
+            control,
+            watcher,
+            ended,
+            stopper: started.stopper,
+            stats: started.stats,
+        })
+    }
+
+    /// Whether this service already runs the backend the executor asks for.
+    pub fn runs(&self, executor: &Executor) -> bool {
+        self.backend == executor.backend && self.grab == executor.grab
+    }
+
+    /// Swaps in the chords of an executor this service [runs](Service::runs).
+    pub fn reload(&mut self, index: usize, executor: Executor) {
+        log::info!("Reloading chord service: {}", &self.backend);
+        let (name, _, _, bindings) = split(executor);
+        self.index = index;
+        self.name = name;
+        self.send(Control::Reload(bindings));
+    }
+
+    /// Stops the backend, which in turn ends the chord handler, and waits for both.
+    pub fn stop(self) {
+        log::info!("Stopping chord service: {}", &self.backend);
+        drop(self.stopper);
+        Self::wait(&self.backend, self.watcher);
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.ended.load(Ordering::Acquire)
+    }
+
+    pub fn join(self) {
+        Self::wait(&self.backend, self.watcher);
+    }
+
+    /// Waits for the watcher, reporting rather than passing on a panic of the service, so that
+    /// the other services keep running.
+    fn wait(backend: &Backend, watcher: JoinHandle<()>) {
+        if watcher.join().is_err() {
+            log::error!("Chord service {backend} panicked");
+        }
+    }
+
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn backend(&self) -> &Backend {
+        &self.backend
+    }
+
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    pub fn selected_by(&self, selector: &Selector) -> bool {
+        selector.selects(self.index, self.name.as_deref())
+    }
+
+    pub fn pause(&self, paused: bool) {
+        self.send(Control::Pause(paused));
+    }
+
+    /// Fires the named chord, returning whether it exists.
+    pub fn trigger(&self, chord: String) -> Option<bool> {
+        let (reply, response) = crossbeam_channel::bounded(1);
+        self.send(Control::Trigger(chord, reply));
+        response.recv_timeout(CONTROL_TIMEOUT).ok()
+    }
+
+    pub fn status(&self) -> Option<Status> {
+        let (reply, response) = crossbeam_channel::bounded(1);
+        self.send(Control::Status(reply));
+        response.recv_timeout(CONTROL_TIMEOUT).ok()
+    }
+
+    fn send(&self, request: Control) {
+        if self.control.send(request).is_err() {
+            log::warn!("Chord service {} is no longer running", &self.backend);
+        }
+    }
+}
+
+fn split(executor: Executor) -> (Option<String>, Backend, bool, Bindings) {
+    let bindings = Bindings {
+        chords: executor.chords,
+        chord_opts: executor.chord_options,
+        shell: executor.shell,
+        modes: executor.modes,
+        max_hold: executor.max_hold_ms.map(Duration::from_millis),
+        max_hold_check: executor.max_hold_check,
+        axis_hysteresis_percent: executor.axis_hysteresis_percent,
+    };
+    (executor.name, executor.backend, executor.grab, bindings)
+}
@@ -0,0 +1,172 @@
+use crate::{
+    config::{self, Config},
+    control::{Request, Response, Selector},
+    service::Service,
+};
+use crossbeam_channel::{Receiver, Sender};
+use serde_json::json;
+use std::path::PathBuf;
+
+/// Owns the running services and keeps them in line with the config file.
+pub struct Supervisor {
+    config_path: PathBuf,
+    services: Vec<Service>,
+    /// Wakes up the main loop when a service stopped on its own.
+    finished: (Sender<()>, Receiver<()>),
+}
+
+impl Supervisor {
+    pub fn new(config_path: PathBuf) -> Self {
+        Self {
+            config_path,
+            services: Vec::new(),
+            finished: crossbeam_channel::unbounded(),
+        }
+    }
+
+    /// Receives a message whenever a service stops, after which [`Supervisor::reap`] should run.
+    pub fn finished(&self) -> Receiver<()> {
+        self.finished.1.clone()
+    }
+
+    /// Whether any service is still running.
+    pub fn is_running(&self) -> bool {
+        self.services.iter().any(|service| !service.is_finished())
+    }
+
+    /// Forgets the services that stopped on their own.
+    pub fn reap(&mut self) {
+        let (finished, running) = std::mem::take(&mut self.services)
+            .into_iter()
+            .partition::<Vec<_>, _>(Service::is_finished);
+        self.services = running;
+        for service in finished {
+            log::warn!("A chord service stopped, it restarts on the next reload");
+            service.join();
+        }
+    }
+
+    /// Loads the config file again, keeping the current config if the new one is invalid.
+    pub fn reload(&mut self) -> anyhow::Result<()> {
+        match config::load_config(Some(self.config_path.clone())) {
+            Ok(config) => {
+                self.apply_config(config);
+                Ok(())
+            }
+            Err(err) => {
+                log::error!("Keeping the current config, the new one is invalid: {err:#}");
+                Err(err)
+            }
+        }
+    }
+
+    /// Brings the running services in line with the config, restarting only the backends whose
+    /// definition changed.
+    pub fn apply_config(&mut self, config: Config) {
+        let mut previous = std::mem::take(&mut self.services);
+        let mut kept = Vec::new();
+        let mut started = Vec::new();
+        for (index, executor) in config.executors.into_iter().enumerate() {
+            match previous.iter().position(|service| service.runs(&executor)) {
+                Some(position) => kept.push((previous.swap_remove(position), index, executor)),
+                None => started.push((index, executor)),
+            }
+        }
+        // stop first, the new services may want to grab the same devices
+        for service in previous {
+            service.stop();
+        }
+        for (mut service, index, executor) in kept {
+            service.reload(index, executor);
+            self.services.push(service);
+        }
+        for (index, executor) in started {
+            match Service::start(index, executor, self.finished.0.clone()) {
+                Ok(service) => self.services.push(service),
+                Err(err) => log::error!("Could not start chord service: {err}"),
+            }
+        }
+        self.reap();
+        self.services.sort_by_key(Service::index);
+    }
+
+    pub fn handle(&mut self, request: Request) -> Response {
+        match request {
+            Request::List => {
+                let executors = self.services.iter().map(|service| {
+                    json!({
+                        "index": service.index() + 1,
+                        "name": service.name(),
+                        "backend": service.backend().to_string(),
+                        "status": service.status(),
+                        "stats": service.stats(),
+                    })
+                });
+                Response::Ok {
+                    data: executors.collect(),
+                }
+            }
+            Request::Pause { executor } => self.pause(&executor, true),
+            Request::Resume { executor } => self.pause(&executor, false),
+            Request::Trigger { executor, chord } => {
+                let Some(service) = self.find(&executor) else {
+                    return unknown_executor(&executor);
+                };
+                match service.trigger(chord.clone()) {
+                    Some(true) => Response::ok(),
+                    Some(false) => Response::error(format!("No chord named `{chord}`")),
+                    None => not_responding(service),
+                }
+            }
+            Request::Reload => match self.reload() {
+                Ok(_) => Response::ok(),
+                Err(err) => Response::error(format!("{err:#}")),
+            },
+            Request::Held { executor } => {
+                let Some(service) = self.find(&executor) else {
+                    return unknown_executor(&executor);
+                };
+                match service.status() {
+                    Some(status) => Response::Ok {
+                        data: json!(status.held),
+                    },
+                    None => not_responding(service),
+                }
+            }
+        }
+    }
+
+    fn pause(&self, selector: &Selector, paused: bool) -> Response {
+        match self.find(selector) {
+            Some(service) => {
+                service.pause(paused);
+                Response::ok()
+            }
+            None => unknown_executor(selector),
+        }
+    }
+
+    fn find(&self, selector: &Selector) -> Option<&Service> {
+        self.services
+            .iter()
+            .find(|service| service.selected_by(selector))
+    }
+
+    /// Stops every remaining service.
+    pub fn stop(self) {
+        for service in self.services {
+            service.stop();
+        }
+    }
+}
+
+fn unknown_executor(selector: &Selector) -> Response {
+    Response::error(format!("No running executor {selector}"))
+}
+
+fn not_responding(service: &Service) -> Response {
+    Response::error(format!(
+        "Executor #{} is not responding",
+        service.index() + 1
+    ))
+}